@@ -0,0 +1,304 @@
+use serde::Serialize;
+
+use crate::frame::Acars;
+
+/// Structured fields decoded from the free text of an ACARS message
+#[derive(Serialize, Default, Debug, Clone)]
+pub struct Report {
+    /// Label and format the text was decoded as
+    pub decoder: &'static str,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lat: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lon: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub altitude: Option<i64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub waypoint: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_waypoint: Option<String>,
+    /// Estimated time of arrival at the next waypoint or destination (UTC)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub eta: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub destination: Option<String>,
+}
+
+impl Report {
+    pub fn position(&self) -> Option<(f64, f64)> {
+        Some((self.lat?, self.lon?))
+    }
+}
+
+/// Parses one coordinate with a leading hemisphere, either as degrees and
+/// tenths of minutes (`N37451`, `W122221`), degrees and decimal minutes
+/// (`N3745.1`) or decimal degrees (`N37.752`). `deg_digits` is 2 for
+/// latitudes and 3 for longitudes.
+fn parse_coord(text: &str, deg_digits: usize) -> Option<f64> {
+    let text = text.trim();
+    let sign = match text.chars().next()? {
+        'N' | 'E' => 1.0,
+        'S' | 'W' => -1.0,
+        _ => return None,
+    };
+    let digits = text.get(1..)?.trim().trim_start_matches('+');
+
+    let value = match digits.find('.') {
+        Some(dot) if dot > deg_digits => {
+            let degrees: f64 = digits.get(..deg_digits)?.parse().ok()?;
+            let minutes: f64 = digits.get(deg_digits..)?.parse().ok()?;
+            (minutes < 60.0).then_some(degrees + minutes / 60.0)?
+        }
+        Some(_) => digits.parse().ok()?,
+        None if digits.len() > deg_digits + 1 && digits.chars().all(|c| c.is_ascii_digit()) => {
+            let degrees: f64 = digits.get(..deg_digits)?.parse().ok()?;
+            let minutes = digits.get(deg_digits..)?;
+            let minutes: f64 = minutes.parse::<f64>().ok()? / 10f64.powi(minutes.len() as i32 - 2);
+            (minutes < 60.0).then_some(degrees + minutes / 60.0)?
+        }
+        None => return None,
+    };
+
+    let limit = if deg_digits == 2 { 90.0 } else { 180.0 };
+    (value <= limit).then_some(sign * value)
+}
+
+/// Parses a latitude immediately followed by a longitude, e.g. `N37451W122221`
+fn parse_position(text: &str) -> Option<(f64, f64)> {
+    let text = text.trim();
+    let split = text.get(1..)?.find(['E', 'W'])? + 1;
+
+    Some((
+        parse_coord(text.get(..split)?, 2)?,
+        parse_coord(text.get(split..)?, 3)?,
+    ))
+}
+
+/// Parses an altitude given either in feet or as a flight level
+fn parse_altitude(text: &str) -> Option<i64> {
+    let text = text.trim();
+    let (text, flight_level) = match text.strip_prefix("FL") {
+        Some(level) => (level, true),
+        None => (text, false),
+    };
+
+    let value: i64 = text.trim_start_matches('+').parse().ok()?;
+    match flight_level || value < 1000 {
+        true => Some(value * 100),
+        false => Some(value),
+    }
+}
+
+/// Formats `HHMM` or `HHMMSS` as `HH:MM[:SS]`
+fn parse_time(text: &str) -> Option<String> {
+    let text = text.trim();
+    if !text.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    match text.len() {
+        4 => Some(format!("{}:{}", &text[..2], &text[2..])),
+        6 => Some(format!("{}:{}:{}", &text[..2], &text[2..4], &text[4..])),
+        _ => None,
+    }
+}
+
+fn waypoint(text: &str) -> Option<String> {
+    let text = text.trim();
+    (!text.is_empty() && text.chars().all(|c| c.is_ascii_alphanumeric())).then(|| text.to_string())
+}
+
+/// ARINC 702 position report, e.g.
+/// `POSN37451W122221,SFO,123456,350,OAK,123556,SJC,M45,27015`: position,
+/// reporting point, time over it, flight level, next point and ETA at it
+fn decode_pos(text: &str) -> Option<Report> {
+    let start = text
+        .match_indices("POS")
+        .map(|(idx, _)| idx + 3)
+        .find(|idx| text[*idx..].starts_with(['N', 'S']))?;
+    let fields: Vec<&str> = text[start..].split(',').collect();
+
+    let (lat, lon) = parse_position(fields[0])?;
+    let field = |idx: usize| fields.get(idx).copied().unwrap_or("");
+
+    Some(Report {
+        decoder: "H1 POS",
+        lat: Some(lat),
+        lon: Some(lon),
+        altitude: parse_altitude(field(3)),
+        waypoint: waypoint(field(1)),
+        next_waypoint: waypoint(field(4)),
+        eta: parse_time(field(5)),
+        ..Default::default()
+    })
+}
+
+/// Airline position reports made of `/KEY VALUE` fields, e.g.
+/// `/POS N3745.1W12222.1/ALT +35000/ETA 0512/DEST KJFK`
+fn decode_fields(decoder: &'static str, text: &str) -> Option<Report> {
+    let mut report = Report {
+        decoder,
+        ..Default::default()
+    };
+
+    for field in text.split('/').skip(1) {
+        let field = field.trim();
+        let (key, value) = match field.split_once(' ') {
+            Some(pair) => pair,
+            None => continue,
+        };
+
+        match key {
+            "POS" => {
+                if let Some((lat, lon)) = parse_position(value) {
+                    report.lat = Some(lat);
+                    report.lon = Some(lon);
+                }
+            }
+            "ALT" | "FL" => report.altitude = parse_altitude(value),
+            "ETA" => report.eta = parse_time(value),
+            "DEST" | "DST" => report.destination = waypoint(value),
+            "NXT" | "NEXT" => report.next_waypoint = waypoint(value),
+            _ => {}
+        }
+    }
+
+    let decoded = report.lat.is_some()
+        || report.altitude.is_some()
+        || report.eta.is_some()
+        || report.destination.is_some();
+    decoded.then_some(report)
+}
+
+/// Decodes the free text of well known message labels
+pub fn decode(acars: &Acars) -> Option<Report> {
+    let text = acars.msg_text.as_deref()?;
+
+    match acars.label.as_deref()? {
+        "H1" => decode_pos(text),
+        "80" => decode_fields("80", text),
+        "5Z" => decode_fields("5Z", text),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-6,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn parse_coord_formats() {
+        assert_close(parse_coord("N37451", 2).unwrap(), 37.0 + 45.1 / 60.0);
+        assert_close(parse_coord("W122221", 3).unwrap(), -(122.0 + 22.1 / 60.0));
+        assert_close(parse_coord("N3745.1", 2).unwrap(), 37.0 + 45.1 / 60.0);
+        assert_close(parse_coord("S33.942", 2).unwrap(), -33.942);
+        assert_close(parse_coord("E +15110.2", 3).unwrap(), 151.0 + 10.2 / 60.0);
+    }
+
+    #[test]
+    fn parse_coord_rejects_malformed() {
+        assert_eq!(parse_coord("", 2), None);
+        assert_eq!(parse_coord("X37451", 2), None);
+        assert_eq!(parse_coord("N", 2), None);
+        assert_eq!(parse_coord("N37", 2), None);
+        assert_eq!(parse_coord("N37A51", 2), None);
+        assert_eq!(parse_coord("N95000", 2), None);
+        assert_eq!(parse_coord("N3790.0", 2), None);
+        assert_eq!(parse_coord("N3é.1", 2), None);
+        assert_eq!(parse_coord("Né", 2), None);
+    }
+
+    #[test]
+    fn parse_position_pairs() {
+        let (lat, lon) = parse_position("N37451W122221").unwrap();
+        assert_close(lat, 37.0 + 45.1 / 60.0);
+        assert_close(lon, -(122.0 + 22.1 / 60.0));
+
+        let (lat, lon) = parse_position(" S3356.5E15110.2 ").unwrap();
+        assert_close(lat, -(33.0 + 56.5 / 60.0));
+        assert_close(lon, 151.0 + 10.2 / 60.0);
+    }
+
+    #[test]
+    fn parse_position_rejects_malformed() {
+        assert_eq!(parse_position(""), None);
+        assert_eq!(parse_position("N37451"), None);
+        assert_eq!(parse_position("N37451W"), None);
+        assert_eq!(parse_position("é12"), None);
+        assert_eq!(parse_position("Nä37W122"), None);
+    }
+
+    #[test]
+    fn parse_altitude_units() {
+        assert_eq!(parse_altitude("35000"), Some(35000));
+        assert_eq!(parse_altitude("+35000"), Some(35000));
+        assert_eq!(parse_altitude("350"), Some(35000));
+        assert_eq!(parse_altitude("FL370"), Some(37000));
+        assert_eq!(parse_altitude(""), None);
+        assert_eq!(parse_altitude("FL"), None);
+        assert_eq!(parse_altitude("M45"), None);
+        assert_eq!(parse_altitude("3é0"), None);
+    }
+
+    #[test]
+    fn decode_pos_report() {
+        let report =
+            decode_pos("POSN37451W122221,SFO,123456,350,OAK,123556,SJC,M45,27015").unwrap();
+        assert_eq!(report.decoder, "H1 POS");
+        assert_close(report.lat.unwrap(), 37.0 + 45.1 / 60.0);
+        assert_close(report.lon.unwrap(), -(122.0 + 22.1 / 60.0));
+        assert_eq!(report.altitude, Some(35000));
+        assert_eq!(report.waypoint.as_deref(), Some("SFO"));
+        assert_eq!(report.next_waypoint.as_deref(), Some("OAK"));
+        assert_eq!(report.eta.as_deref(), Some("12:35:56"));
+    }
+
+    #[test]
+    fn decode_pos_rejects_malformed() {
+        assert!(decode_pos("").is_none());
+        assert!(decode_pos("POSITION UNKNOWN").is_none());
+        assert!(decode_pos("POSN37451").is_none());
+        assert!(decode_pos("POSNé,SFO").is_none());
+
+        let report = decode_pos("POSN37451W122221").unwrap();
+        assert_eq!(report.altitude, None);
+        assert_eq!(report.waypoint, None);
+    }
+
+    #[test]
+    fn decode_fields_report() {
+        let report =
+            decode_fields("80", "/POS N3745.1W12222.1/ALT +35000/ETA 0512/DEST KJFK").unwrap();
+        assert_eq!(report.decoder, "80");
+        assert_close(report.lat.unwrap(), 37.0 + 45.1 / 60.0);
+        assert_close(report.lon.unwrap(), -(122.0 + 22.1 / 60.0));
+        assert_eq!(report.altitude, Some(35000));
+        assert_eq!(report.eta.as_deref(), Some("05:12"));
+        assert_eq!(report.destination.as_deref(), Some("KJFK"));
+
+        let report = decode_fields("5Z", "/FL 370/NXT ALCOA").unwrap();
+        assert_eq!(report.altitude, Some(37000));
+        assert_eq!(report.next_waypoint.as_deref(), Some("ALCOA"));
+    }
+
+    #[test]
+    fn decode_fields_rejects_malformed() {
+        assert!(decode_fields("80", "").is_none());
+        assert!(decode_fields("80", "no fields here").is_none());
+        assert!(decode_fields("80", "/POS é12").is_none());
+        assert!(decode_fields("80", "/ALT/ETA/DEST").is_none());
+        assert!(decode_fields("80", "/DEST KJ FK").is_none());
+        assert!(decode_fields("80", "/ETA 05é2").is_none());
+    }
+}
@@ -0,0 +1,226 @@
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+
+use crate::acars;
+use crate::frame::MessageFrame;
+use crate::reference::Reference;
+use crate::util::unix_now;
+
+const EXPIRE_INTERVAL_SECS: u64 = 60;
+
+#[derive(Serialize, Clone, Debug)]
+pub struct AircraftState {
+    pub icao: String,
+    pub registration: Option<String>,
+    pub flight_id: Option<String>,
+    /// ICAO type designator and operator from the reference files
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    pub aircraft_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub operator: Option<String>,
+
+    pub lat: Option<f64>,
+    pub lon: Option<f64>,
+    pub altitude: Option<i64>,
+    /// Last time a position was reported
+    pub position_updated: Option<u64>,
+
+    /// Ground station and frequency (kHz) the aircraft was last heard on
+    pub gs: u32,
+    pub freq: u32,
+
+    pub first_seen: u64,
+    pub last_seen: u64,
+    pub frames: u64,
+}
+
+/// Latitude, longitude and altitude (ft) of the first ADS-C basic report
+/// carried in a frame, if any
+fn adsc_report(frame: &Value) -> Option<(f64, f64, i64)> {
+    frame
+        .pointer("/hfdl/lpdu/hfnpdu/acars/arinc622/adsc/tags")?
+        .as_array()?
+        .iter()
+        .find_map(|tag| {
+            let report = tag.get("basic_report")?;
+            Some((
+                report.get("lat")?.as_f64()?,
+                report.get("lon")?.as_f64()?,
+                report.get("alt")?.as_f64()?.round() as i64,
+            ))
+        })
+}
+
+/// Per aircraft state assembled from link-layer traffic, keyed by ICAO
+/// address. Aircraft are only identified by a per ground station id in most
+/// frames, so ids are mapped back to ICAO addresses from logon confirms.
+pub struct AircraftTable {
+    expiry: Duration,
+    last_expired: u64,
+
+    logons: HashMap<(u32, u8), (String, u64)>,
+    aircraft: BTreeMap<String, AircraftState>,
+
+    reference: Option<Reference>,
+}
+
+impl AircraftTable {
+    pub fn new(expiry: Duration, reference: Option<Reference>) -> Self {
+        AircraftTable {
+            expiry,
+            last_expired: 0,
+            logons: HashMap::new(),
+            aircraft: BTreeMap::new(),
+            reference,
+        }
+    }
+
+    /// Returns the state of the aircraft the frame was attributed to and
+    /// whether the frame reported its position
+    pub fn update(&mut self, msg: &MessageFrame, frame: &Value) -> Option<(&AircraftState, bool)> {
+        let now = unix_now();
+        if now.saturating_sub(self.last_expired) >= EXPIRE_INTERVAL_SECS {
+            self.expire(now);
+        }
+
+        let lpdu = match &msg.hfdl.lpdu {
+            Some(lpdu) => lpdu,
+            None => return None,
+        };
+
+        let (aircraft, gs) = if lpdu.src.is_aircraft() {
+            (&lpdu.src, &lpdu.dst)
+        } else if lpdu.dst.is_aircraft() {
+            (&lpdu.dst, &lpdu.src)
+        } else {
+            return None;
+        };
+        let gs_id = gs.id as u32;
+
+        if let (Some(info), Some(ac_id)) = (&lpdu.ac_info, lpdu.assigned_ac_id) {
+            self.logons.insert((gs_id, ac_id), (info.icao.clone(), now));
+        }
+
+        let icao = aircraft
+            .ac_info
+            .as_ref()
+            .or(lpdu.ac_info.as_ref())
+            .map(|info| info.icao.clone())
+            .or_else(|| {
+                self.logons
+                    .get(&(gs_id, aircraft.id))
+                    .map(|(icao, _)| icao.clone())
+            })?;
+
+        let state = self
+            .aircraft
+            .entry(icao.clone())
+            .or_insert_with(|| AircraftState {
+                icao,
+                registration: None,
+                flight_id: None,
+                aircraft_type: None,
+                operator: None,
+                lat: None,
+                lon: None,
+                altitude: None,
+                position_updated: None,
+                gs: gs_id,
+                freq: 0,
+                first_seen: now,
+                last_seen: now,
+                frames: 0,
+            });
+
+        state.gs = gs_id;
+        state.freq = msg.hfdl.freq_khz();
+        state.last_seen = now;
+        state.frames += 1;
+        let mut positioned = false;
+
+        if let Some(hfnpdu) = &lpdu.hfnpdu {
+            if let Some(flight_id) = hfnpdu.flight_id.as_ref().filter(|f| !f.is_empty()) {
+                state.flight_id = Some(flight_id.clone());
+            }
+            if let Some(pos) = hfnpdu.pos.as_ref().filter(|pos| pos.is_valid()) {
+                state.lat = Some(pos.lat);
+                state.lon = Some(pos.lon);
+                state.position_updated = Some(now);
+                positioned = true;
+            }
+            if let Some(acars) = &hfnpdu.acars {
+                if let Some(reg) = acars.registration() {
+                    state.registration = Some(reg.to_string());
+                }
+                if state.flight_id.is_none() {
+                    state.flight_id = acars.flight.clone();
+                }
+                if let Some(report) = acars::decode(acars) {
+                    if let Some((lat, lon)) = report.position() {
+                        state.lat = Some(lat);
+                        state.lon = Some(lon);
+                        state.position_updated = Some(now);
+                        positioned = true;
+                    }
+                    if report.altitude.is_some() {
+                        state.altitude = report.altitude;
+                    }
+                }
+            }
+        }
+
+        if let Some((lat, lon, altitude)) = adsc_report(frame) {
+            state.lat = Some(lat);
+            state.lon = Some(lon);
+            state.altitude = Some(altitude);
+            state.position_updated = Some(now);
+            positioned = true;
+        }
+
+        if let Some(reference) = self.reference.as_mut() {
+            reference.refresh();
+
+            if let Some(info) = reference.aircraft(&state.icao) {
+                if state.registration.is_none() {
+                    state.registration = info.registration.clone();
+                }
+                state.aircraft_type = info.aircraft_type.clone();
+            }
+            if let Some(flight_id) = &state.flight_id {
+                state.operator = reference.operator(flight_id).map(str::to_string);
+            }
+        }
+
+        Some((state, positioned))
+    }
+
+    pub fn has_reference(&self) -> bool {
+        self.reference.is_some()
+    }
+
+    fn expire(&mut self, now: u64) {
+        let expiry = self.expiry.as_secs();
+
+        self.aircraft
+            .retain(|_, state| now.saturating_sub(state.last_seen) < expiry);
+        self.logons
+            .retain(|_, (_, seen)| now.saturating_sub(*seen) < expiry);
+        self.last_expired = now;
+    }
+
+    /// Aircraft heard within the expiry window
+    pub fn active(&self) -> impl Iterator<Item = &AircraftState> {
+        let now = unix_now();
+        let expiry = self.expiry.as_secs();
+
+        self.aircraft
+            .values()
+            .filter(move |state| now.saturating_sub(state.last_seen) < expiry)
+    }
+
+    pub fn snapshot(&self) -> Value {
+        serde_json::to_value(self.active().collect::<Vec<&AircraftState>>()).unwrap_or(Value::Null)
+    }
+}
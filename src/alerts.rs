@@ -0,0 +1,378 @@
+use log::*;
+use regex::Regex;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::frame::{annotate, MessageFrame};
+use crate::http::{self, Poster};
+use crate::util::unix_now;
+
+/// A watchlist entry frames are checked against
+#[derive(Debug, Clone)]
+pub enum Watch {
+    Icao(String),
+    Registration(String),
+    Flight(String),
+    /// Regular expression matched against ACARS message text
+    Text(Regex),
+}
+
+impl Watch {
+    /// Parses `icao=HEX`, `reg=REGISTRATION`, `flight=FLIGHT` or `text=REGEX`
+    pub fn parse(spec: &str) -> Result<Watch, String> {
+        let (kind, value) = match spec.split_once('=') {
+            Some((kind, value)) if !value.trim().is_empty() => (kind.trim(), value.trim()),
+            _ => return Err(format!("Watch is not KIND=VALUE: {}", spec)),
+        };
+
+        match kind {
+            "icao" => Ok(Watch::Icao(value.to_uppercase())),
+            "reg" => Ok(Watch::Registration(value.to_uppercase())),
+            "flight" => Ok(Watch::Flight(value.to_uppercase())),
+            "text" => Regex::new(value)
+                .map(Watch::Text)
+                .map_err(|e| format!("Invalid watch regex '{}': {}", value, e)),
+            _ => Err(format!(
+                "Watch is not icao=HEX, reg=REGISTRATION, flight=FLIGHT or text=REGEX: {}",
+                spec
+            )),
+        }
+    }
+
+    fn matches(&self, msg: &MessageFrame) -> bool {
+        let lpdu = msg.hfdl.lpdu.as_ref();
+        let acars = msg.acars();
+
+        match self {
+            Watch::Icao(icao) => lpdu.is_some_and(|lpdu| {
+                [&lpdu.src, &lpdu.dst]
+                    .into_iter()
+                    .filter_map(|entity| entity.ac_info.as_ref())
+                    .chain(lpdu.ac_info.as_ref())
+                    .any(|info| info.icao.eq_ignore_ascii_case(icao))
+            }),
+            Watch::Registration(reg) => acars
+                .and_then(|acars| acars.registration())
+                .is_some_and(|r| r.eq_ignore_ascii_case(reg)),
+            Watch::Flight(flight) => lpdu
+                .and_then(|lpdu| lpdu.hfnpdu.as_ref())
+                .and_then(|hfnpdu| hfnpdu.flight_id.as_deref())
+                .into_iter()
+                .chain(acars.and_then(|acars| acars.flight.as_deref()))
+                .any(|f| f.trim().eq_ignore_ascii_case(flight)),
+            Watch::Text(regex) => acars
+                .and_then(|acars| acars.msg_text.as_deref())
+                .is_some_and(|text| regex.is_match(text)),
+        }
+    }
+}
+
+impl std::fmt::Display for Watch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Watch::Icao(icao) => write!(f, "icao={}", icao),
+            Watch::Registration(reg) => write!(f, "reg={}", reg),
+            Watch::Flight(flight) => write!(f, "flight={}", flight),
+            Watch::Text(regex) => write!(f, "text={}", regex),
+        }
+    }
+}
+
+/// What happens when a frame matches the watchlist
+#[derive(Debug, Clone)]
+pub enum AlertSpec {
+    Log,
+    Exec(String),
+    Webhook(url::Url),
+}
+
+impl AlertSpec {
+    /// Parses `log`, `exec:COMMAND` or an `http(s)://` webhook URL
+    pub fn parse(spec: &str) -> Result<AlertSpec, String> {
+        if spec == "log" {
+            return Ok(AlertSpec::Log);
+        }
+        if http::is_url(spec) {
+            return http::parse_url(spec).map(AlertSpec::Webhook);
+        }
+
+        match spec.split_once(':') {
+            Some(("exec", command)) if !command.trim().is_empty() => {
+                Ok(AlertSpec::Exec(command.to_string()))
+            }
+            _ => Err(format!(
+                "Alert is not log, exec:COMMAND or http(s)://URL: {}",
+                spec
+            )),
+        }
+    }
+}
+
+enum Alert {
+    Log,
+    Exec(String),
+    Webhook(Poster),
+}
+
+impl Alert {
+    fn open(spec: &AlertSpec) -> Alert {
+        match spec {
+            AlertSpec::Log => Alert::Log,
+            AlertSpec::Exec(command) => Alert::Exec(command.clone()),
+            AlertSpec::Webhook(url) => Alert::Webhook(Poster::start(url.clone())),
+        }
+    }
+
+    fn raise(&self, watch: &Watch, frame: &Value) {
+        match self {
+            Alert::Log => warn!(
+                "[alert] {} matched frame on {} kHz",
+                watch,
+                frame
+                    .pointer("/hfdl/freq")
+                    .and_then(|freq| freq.as_u64())
+                    .map(|freq| freq / 1000)
+                    .unwrap_or_default()
+            ),
+            Alert::Exec(command) => run(command.clone(), watch.to_string(), frame.to_string()),
+            Alert::Webhook(poster) => poster.send(frame.to_string()),
+        }
+    }
+}
+
+/// Runs `command` through the shell with the frame on stdin, off the frame
+/// processing thread
+fn run(command: String, watch: String, frame: String) {
+    thread::spawn(move || {
+        let child = Command::new("sh")
+            .arg("-c")
+            .arg(&command)
+            .env("VIPER_ALERT_WATCH", &watch)
+            .stdin(Stdio::piped())
+            .spawn();
+
+        let mut child = match child {
+            Ok(child) => child,
+            Err(e) => {
+                error!("Unable to run alert command '{}': {}", command, e);
+                return;
+            }
+        };
+
+        if let Some(mut stdin) = child.stdin.take() {
+            if let Err(e) = writeln!(stdin, "{}", frame) {
+                warn!("Unable to pass frame to alert command '{}': {}", command, e);
+            }
+        }
+
+        match child.wait() {
+            Ok(status) if status.success() => {}
+            Ok(status) => warn!("Alert command '{}' exited with {}", command, status),
+            Err(e) => error!("Alert command '{}' failed: {}", command, e),
+        }
+    });
+}
+
+/// Checks frames against the watchlist and raises alerts, at most one per
+/// watchlist entry every `interval`
+pub struct Alerter {
+    watches: Vec<Watch>,
+    alerts: Vec<Alert>,
+    interval: Duration,
+    last_raised: HashMap<usize, Instant>,
+}
+
+impl Alerter {
+    pub fn new(watches: &[Watch], alerts: &[AlertSpec], interval: Duration) -> Self {
+        Alerter {
+            watches: watches.to_vec(),
+            alerts: alerts.iter().map(Alert::open).collect(),
+            interval,
+            last_raised: HashMap::new(),
+        }
+    }
+
+    /// Indexes of the watchlist entries the frame matches and that are due
+    /// for an alert at `now`
+    fn due(&mut self, msg: &MessageFrame, now: Instant) -> Vec<usize> {
+        let mut due = vec![];
+
+        for (idx, watch) in self.watches.iter().enumerate() {
+            if !watch.matches(msg) {
+                continue;
+            }
+            if let Some(last) = self.last_raised.get(&idx) {
+                if now.duration_since(*last) < self.interval {
+                    debug!("[alert] {} matched again, rate limited", watch);
+                    continue;
+                }
+            }
+            self.last_raised.insert(idx, now);
+            due.push(idx);
+        }
+
+        due
+    }
+
+    pub fn check(&mut self, msg: &MessageFrame, frame: &Value) {
+        for idx in self.due(msg, Instant::now()) {
+            let watch = &self.watches[idx];
+
+            let mut alerted = frame.clone();
+            annotate(
+                &mut alerted,
+                "alert",
+                json!({"watch": watch.to_string(), "ts": unix_now()}),
+            );
+            for alert in self.alerts.iter() {
+                alert.raise(watch, &alerted);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Read};
+    use std::net::TcpListener;
+
+    /// Logon confirm of A1B2C3, then an ACARS message from N123UA as UAL123
+    fn frames() -> Vec<(MessageFrame, Value)> {
+        let logon = json!({"hfdl": {"freq": 13276000, "lpdu": {
+            "err": false,
+            "src": {"type": "Ground station", "id": 1},
+            "dst": {"type": "Aircraft", "id": 0},
+            "type": {"id": 159, "name": "Logon confirm"},
+            "ac_info": {"icao": "A1B2C3"},
+            "assigned_ac_id": 12
+        }}});
+        let acars = json!({"hfdl": {"freq": 8927000, "lpdu": {
+            "err": false,
+            "src": {"type": "Aircraft", "id": 12},
+            "dst": {"type": "Ground station", "id": 1},
+            "hfnpdu": {"acars": {
+                "reg": ".N123UA",
+                "flight": "UA0123",
+                "label": "H1",
+                "msg_text": "POSRPT 3530N14015W FL350"
+            }}
+        }}});
+
+        [logon, acars]
+            .into_iter()
+            .map(|frame| (MessageFrame::from_value(&frame).unwrap(), frame))
+            .collect()
+    }
+
+    fn matching(spec: &str) -> Vec<bool> {
+        let watch = Watch::parse(spec).unwrap();
+        frames().iter().map(|(msg, _)| watch.matches(msg)).collect()
+    }
+
+    #[test]
+    fn parses_watches() {
+        assert_eq!(
+            Watch::parse("icao=a1b2c3").unwrap().to_string(),
+            "icao=A1B2C3"
+        );
+        assert_eq!(
+            Watch::parse(" reg = n123ua").unwrap().to_string(),
+            "reg=N123UA"
+        );
+        assert_eq!(
+            Watch::parse("text=POS.*FL3").unwrap().to_string(),
+            "text=POS.*FL3"
+        );
+
+        for bad in ["icao", "icao=", "tail=N123UA", "text=("] {
+            assert!(Watch::parse(bad).is_err(), "{} parsed", bad);
+        }
+    }
+
+    #[test]
+    fn watches_match_their_fields() {
+        assert_eq!(matching("icao=a1b2c3"), vec![true, false]);
+        assert_eq!(matching("icao=c0ffee"), vec![false, false]);
+        assert_eq!(matching("reg=N123UA"), vec![false, true]);
+        assert_eq!(matching("flight=ua0123"), vec![false, true]);
+        assert_eq!(matching("text=\\d{4}N\\d{5}W"), vec![false, true]);
+        assert_eq!(matching("text=^FL350"), vec![false, false]);
+    }
+
+    #[test]
+    fn alerts_are_rate_limited_per_watch() {
+        let watches = [
+            Watch::parse("icao=A1B2C3").unwrap(),
+            Watch::parse("reg=N123UA").unwrap(),
+        ];
+        let mut alerter = Alerter::new(&watches, &[], Duration::from_secs(300));
+        let frames = frames();
+        let start = Instant::now();
+
+        assert_eq!(alerter.due(&frames[0].0, start), vec![0]);
+        assert_eq!(alerter.due(&frames[1].0, start), vec![1]);
+        assert!(alerter
+            .due(&frames[0].0, start + Duration::from_secs(299))
+            .is_empty());
+        assert_eq!(
+            alerter.due(&frames[0].0, start + Duration::from_secs(300)),
+            vec![0]
+        );
+    }
+
+    #[test]
+    fn webhook_receives_annotated_frame() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let watches = [Watch::parse("reg=N123UA").unwrap()];
+        let alerts = [AlertSpec::parse(&url).unwrap()];
+        let mut alerter = Alerter::new(&watches, &alerts, Duration::from_secs(300));
+
+        let (msg, frame) = &frames()[1];
+        alerter.check(msg, frame);
+
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(&stream);
+        let mut length = 0;
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if line.trim().is_empty() {
+                break;
+            }
+            if let Some(value) = line.to_lowercase().strip_prefix("content-length:") {
+                length = value.trim().parse().unwrap();
+            }
+        }
+        let mut body = vec![0u8; length];
+        reader.read_exact(&mut body).unwrap();
+        (&stream)
+            .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+            .unwrap();
+
+        let posted: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(posted["hfdl"], frame["hfdl"]);
+        assert_eq!(posted["viper"]["alert"]["watch"], json!("reg=N123UA"));
+        assert!(posted["viper"]["alert"]["ts"].as_u64().unwrap() > 0);
+    }
+
+    #[test]
+    fn parses_alert_specs() {
+        assert!(matches!(AlertSpec::parse("log"), Ok(AlertSpec::Log)));
+        assert!(matches!(
+            AlertSpec::parse("exec:mail -s viper me@example.com"),
+            Ok(AlertSpec::Exec(_))
+        ));
+        assert!(matches!(
+            AlertSpec::parse("https://hooks.example.com/viper"),
+            Ok(AlertSpec::Webhook(_))
+        ));
+        assert!(AlertSpec::parse("exec: ").is_err());
+        assert!(AlertSpec::parse("mail").is_err());
+    }
+}
@@ -1,22 +1,97 @@
-use clap::Parser;
+use clap::{Parser, Subcommand, ValueEnum};
 use std::collections::HashMap;
 use std::path::PathBuf;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 pub struct Args {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
     /// Path to dumphfdl binary
     #[arg(long, value_name = "FILE", default_value = "/usr/bin/dumphfdl")]
     pub bin: PathBuf,
 
     /// Path to dumphfdl system table configuration
-    #[arg(long, value_name = "FILE", default_value = "/etc/systable.conf")]
+    #[arg(
+        long,
+        value_name = "FILE",
+        default_value = "/etc/systable.conf",
+        global = true
+    )]
     pub sys_table: PathBuf,
 
     /// SoapySDR driver configuration (override w/ VIPER_SOAPY_DRIVER)
     #[arg(long, value_name = "DRIVER", default_value = "driver=airspyhf")]
     pub driver: String,
 
+    /// End-to-end SDR gain in dB (default: autogain)
+    #[arg(long, value_name = "DB")]
+    pub gain: Option<f64>,
+
+    /// Per-band gain override in dB (repeatable)
+    #[arg(long, value_name = "BAND=DB")]
+    pub band_gain: Vec<String>,
+
+    /// SDR gain elements, e.g. LNA=10,RF=20
+    #[arg(long, value_name = "ELEM=VALUE,...")]
+    pub gain_elements: Option<String>,
+
+    /// SDR antenna port
+    #[arg(long, value_name = "ANTENNA")]
+    pub antenna: Option<String>,
+
+    /// Per-band antenna port override (repeatable)
+    #[arg(long, value_name = "BAND=ANTENNA")]
+    pub band_antenna: Vec<String>,
+
+    /// SDR frequency correction in ppm
+    #[arg(long, value_name = "PPM", allow_negative_numbers = true)]
+    pub freq_correction: Option<f64>,
+
+    /// Apply the frequency correction estimated from ground station
+    /// frequency skew on the next dumphfdl launch
+    #[arg(long, default_value_t = false)]
+    pub auto_freq_correction: bool,
+
+    /// Learn a gain per band between sessions from the measured noise floor
+    /// and SNR, overriding --gain and --band-gain once learned
+    #[arg(long, default_value_t = false)]
+    pub auto_gain: bool,
+
+    /// File the learned per-band gains are persisted to
+    #[arg(long, value_name = "FILE")]
+    pub gain_file: Option<PathBuf>,
+
+    /// SDR device-specific settings, e.g. biastee=true
+    #[arg(long, value_name = "KEY=VALUE,...")]
+    pub device_settings: Option<String>,
+
+    /// Offset in kHz from the middle of the band to tune the SDR to
+    #[arg(long, value_name = "KHZ", allow_negative_numbers = true)]
+    pub centerfreq_offset: Option<i32>,
+
+    /// Additional SDRs to run dumphfdl on, each listening to a distinct band
+    /// (repeatable; replaces --driver when given)
+    #[arg(long, value_name = "DRIVER[@MAX_SAMPLE_RATE]")]
+    pub sdr: Vec<String>,
+
+    /// Read IQ samples from a recording instead of a SoapySDR device
+    #[arg(long, value_name = "FILE")]
+    pub iq_file: Option<PathBuf>,
+
+    /// Sample format of the IQ recording (cu8, cs16 or cf32)
+    #[arg(long, value_name = "FORMAT", default_value = "cs16")]
+    pub iq_format: String,
+
+    /// Sample rate of the IQ recording in Hz
+    #[arg(long, value_name = "HZ")]
+    pub iq_sample_rate: Option<u32>,
+
+    /// Center frequency of the IQ recording in kHz
+    #[arg(long, value_name = "KHZ")]
+    pub iq_centerfreq: Option<u32>,
+
     /// Methodology for changing HFDL bands (override w/ VIPER_CHOOSER)
     #[arg(
         long,
@@ -33,10 +108,139 @@ pub struct Args {
     #[arg(short, long, default_value_t = false)]
     pub quiet: bool,
 
+    /// Band to never listen to (repeatable)
+    #[arg(long, value_name = "BAND")]
+    pub exclude_band: Vec<u32>,
+
+    /// Frequency in kHz to never listen to, e.g. a local birdie (repeatable)
+    #[arg(long, value_name = "KHZ")]
+    pub exclude_freq: Vec<u32>,
+
+    /// Named band made of hand-picked frequencies in kHz, usable wherever a
+    /// band is expected (repeatable)
+    #[arg(long, value_name = "NAME=KHZ,KHZ,...")]
+    pub custom_band: Vec<String>,
+
     /// Timeout in seconds to wait before switching HF bands
     #[arg(short, long, value_name = "SECONDS", default_value_t = 150)]
     pub timeout: u32,
 
+    /// Per-band timeout override in seconds (repeatable)
+    #[arg(long, value_name = "BAND=SECONDS")]
+    pub band_timeout: Vec<String>,
+
+    /// Seconds a ground station's squitter-reported frequencies are trusted
+    #[arg(long, value_name = "SECONDS", default_value_t = 600)]
+    pub squitter_expiry: u32,
+
+    /// Seconds an aircraft is kept after it was last heard
+    #[arg(long, value_name = "SECONDS", default_value_t = 3600)]
+    pub aircraft_expiry: u32,
+
+    /// Periodically write aircraft positions to this file in readsb's
+    /// aircraft.json format, e.g. for tar1090
+    #[arg(long, value_name = "FILE")]
+    pub aircraft_json: Option<PathBuf>,
+
+    /// Seconds between aircraft.json updates
+    #[arg(long, value_name = "SECONDS", default_value_t = 5, value_parser = clap::value_parser!(u32).range(1..))]
+    pub aircraft_json_interval: u32,
+
+    /// CSV file of `ICAO,REGISTRATION,TYPE` lines aircraft are enriched from,
+    /// reloaded when it changes
+    #[arg(long, value_name = "FILE")]
+    pub aircraft_db: Option<PathBuf>,
+
+    /// CSV file of `CODE,NAME` lines mapping airline ICAO or IATA designators
+    /// to operator names, reloaded when it changes
+    #[arg(long, value_name = "FILE")]
+    pub airline_db: Option<PathBuf>,
+
+    /// Append every aircraft position report to this JSON lines file, which
+    /// `viper export` turns into tracks
+    #[arg(long, value_name = "FILE")]
+    pub track_log: Option<PathBuf>,
+
+    /// Append aircraft logon, logoff and logon denied events to this JSON
+    /// lines file
+    #[arg(long, value_name = "FILE")]
+    pub event_log: Option<PathBuf>,
+
+    /// Address to serve aircraft positions on in BaseStation (SBS-1) format,
+    /// e.g. 0.0.0.0:30003
+    #[arg(long, value_name = "ADDR")]
+    pub sbs_addr: Option<String>,
+
+    /// Quarantine bands that look jammed by local interference for this many
+    /// seconds
+    #[arg(long, value_name = "SECONDS")]
+    pub quarantine_cooldown: Option<u32>,
+
+    /// Fraction of frames with decode errors above which a band is quarantined
+    #[arg(long, value_name = "RATE", default_value_t = 0.5)]
+    pub quarantine_error_rate: f64,
+
+    /// dB a band's noise floor may rise above its baseline before the band is
+    /// quarantined
+    #[arg(long, value_name = "DB", default_value_t = 10.0)]
+    pub quarantine_noise_margin: f64,
+
+    /// Add the ground station's name and coordinates to every frame
+    #[arg(long, default_value_t = false)]
+    pub enrich_gs: bool,
+
+    /// Receiver location in decimal degrees, e.g. 37.62,-122.38; implies
+    /// --enrich-gs and adds the ground station's distance and bearing
+    #[arg(long, value_name = "LAT,LON", allow_hyphen_values = true)]
+    pub receiver_location: Option<String>,
+
+    /// Name identifying this viper instance in tagged frames
+    #[arg(long, value_name = "NAME", default_value = "viper")]
+    pub station: String,
+
+    /// Merge copies of a frame heard by several receivers within this many
+    /// milliseconds into one frame tagged with every receiver
+    #[arg(long, value_name = "MILLISECONDS")]
+    pub dedupe_window: Option<u64>,
+
+    /// UDP multicast group used to coordinate bands with other viper instances,
+    /// e.g. 239.255.72.70:5170
+    #[arg(long, value_name = "GROUP:PORT")]
+    pub cluster: Option<String>,
+
+    /// Address to serve the JSON status API on, e.g. 127.0.0.1:8080
+    #[arg(long, value_name = "ADDR")]
+    pub status_addr: Option<String>,
+
+    /// Seconds between ground station status log summaries (0 disables)
+    #[arg(long, value_name = "SECONDS", default_value_t = 600)]
+    pub summary_interval: u32,
+
+    /// Named frame sink: stdout, file:PATH, udp:HOST:PORT or an http(s):// URL
+    /// frames are POSTed to, e.g. es=http://localhost:9200/hfdl/_doc
+    /// (repeatable)
+    #[arg(long, value_name = "NAME=SINK")]
+    pub sink: Vec<String>,
+
+    /// Frame rule `CONDITION[,CONDITION...] -> ACTION` applied in order, e.g.
+    /// "type=spdu -> route:squitters" (repeatable)
+    #[arg(long, value_name = "RULE")]
+    pub rule: Vec<String>,
+
+    /// Watchlist entry raising alerts when a frame matches: icao=HEX,
+    /// reg=REGISTRATION, flight=FLIGHT or text=REGEX on ACARS text (repeatable)
+    #[arg(long, value_name = "KIND=VALUE")]
+    pub watch: Vec<String>,
+
+    /// What a watchlist match does: log, exec:COMMAND (frame on stdin) or an
+    /// http(s):// webhook URL the frame is POSTed to (repeatable, default: log)
+    #[arg(long, value_name = "ALERT")]
+    pub alert: Vec<String>,
+
+    /// Minimum seconds between alerts for the same watchlist entry
+    #[arg(long, value_name = "SECONDS", default_value_t = 300)]
+    pub alert_interval: u32,
+
     /// Output parameters passthrough to dumphfdl
     #[arg(short, long, value_name = "OUTPUT")]
     pub output: Option<String>,
@@ -68,3 +272,30 @@ impl Args {
         (name, props)
     }
 }
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Export ground stations and aircraft tracks for GIS tools
+    Export(ExportArgs),
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum ExportFormat {
+    Geojson,
+    Kml,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct ExportArgs {
+    /// Output format
+    #[arg(long, value_enum, default_value_t = ExportFormat::Geojson)]
+    pub format: ExportFormat,
+
+    /// Track log written with --track-log to draw aircraft tracks from
+    #[arg(long, value_name = "FILE")]
+    pub tracks: Option<PathBuf>,
+
+    /// File to write the export to (default: stdout)
+    #[arg(short, long, value_name = "FILE")]
+    pub output: Option<PathBuf>,
+}
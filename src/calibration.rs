@@ -0,0 +1,159 @@
+use log::*;
+use std::collections::HashMap;
+
+use crate::frame::MessageFrame;
+
+const MIN_SAMPLES: usize = 10;
+const MAX_PLAUSIBLE_PPM: f64 = 50.0;
+
+/// Estimates every receiver's frequency error from the skew dumphfdl
+/// measures on ground station transmissions, whose carriers are accurate
+/// enough to serve as a reference.
+pub struct Calibration {
+    samples: HashMap<usize, Vec<f64>>,
+    corrections: HashMap<usize, f64>,
+}
+
+fn median(values: &mut [f64]) -> f64 {
+    values.sort_by(|a, b| a.total_cmp(b));
+
+    let mid = values.len() / 2;
+    if values.len().is_multiple_of(2) {
+        (values[mid - 1] + values[mid]) / 2.0
+    } else {
+        values[mid]
+    }
+}
+
+impl Calibration {
+    pub fn new() -> Self {
+        Calibration {
+            samples: HashMap::new(),
+            corrections: HashMap::new(),
+        }
+    }
+
+    pub fn update(&mut self, sdr_idx: usize, msg: &MessageFrame) {
+        let from_gs = msg.hfdl.spdu.is_some()
+            || msg
+                .hfdl
+                .lpdu
+                .as_ref()
+                .is_some_and(|lpdu| lpdu.src.is_ground_station());
+        if !from_gs || msg.hfdl.freq == 0 {
+            return;
+        }
+
+        if let Some(skew) = msg.hfdl.freq_skew {
+            let ppm = skew / msg.hfdl.freq as f64 * 1e6;
+            if ppm.abs() <= MAX_PLAUSIBLE_PPM {
+                self.samples.entry(sdr_idx).or_default().push(ppm);
+            }
+        }
+    }
+
+    /// Correction learned for the receiver, if any has been applied
+    pub fn correction(&self, sdr_idx: usize) -> Option<f64> {
+        self.corrections.get(&sdr_idx).copied()
+    }
+
+    /// Estimates the frequency error of every receiver from the samples
+    /// gathered this session and logs the recommended correction. With
+    /// `apply`, the recommendation is used for the receiver's next launch.
+    pub fn end_session(&mut self, current: &HashMap<usize, f64>, apply: bool) {
+        for (sdr_idx, mut samples) in self.samples.drain() {
+            if samples.len() < MIN_SAMPLES {
+                debug!(
+                    "Receiver {} only has {} freq_skew samples, not enough to calibrate",
+                    sdr_idx,
+                    samples.len()
+                );
+                continue;
+            }
+
+            let skew = median(&mut samples);
+            let in_effect = current.get(&sdr_idx).copied().unwrap_or(0.0);
+            let recommended = ((in_effect - skew) * 100.0).round() / 100.0;
+
+            info!(
+                "Receiver {} measured {:+.2} ppm skew over {} ground station frames, recommended --freq-correction {}",
+                sdr_idx,
+                skew,
+                samples.len(),
+                recommended
+            );
+
+            if apply {
+                self.corrections.insert(sdr_idx, recommended);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const GS: &str = "Ground station";
+
+    /// Frame on 13276 kHz measured with a skew of `ppm`, sent by `src` to `dst`
+    fn frame(ppm: f64, src: &str, dst: &str) -> MessageFrame {
+        let frame = json!({"hfdl": {
+            "freq": 13276000,
+            "freq_skew": ppm * 13.276,
+            "lpdu": {
+                "err": false,
+                "src": {"type": src, "id": 1},
+                "dst": {"type": dst, "id": 2}
+            }
+        }});
+        MessageFrame::from_value(&frame).unwrap()
+    }
+
+    fn learn(calibration: &mut Calibration, sdr_idx: usize, ppms: &[f64]) {
+        for ppm in ppms.iter() {
+            calibration.update(sdr_idx, &frame(*ppm, GS, "Aircraft"));
+        }
+    }
+
+    #[test]
+    fn median_of_odd_and_even_counts() {
+        assert_eq!(median(&mut [3.0, -1.0, 2.0]), 2.0);
+        assert_eq!(median(&mut [4.0, -1.0, 2.0, 1.0]), 1.5);
+    }
+
+    #[test]
+    fn recommends_median_skew_of_ground_station_frames() {
+        let mut calibration = Calibration::new();
+        learn(&mut calibration, 0, &[2.0; 9]);
+        learn(&mut calibration, 0, &[1.5, 40.0, -30.0]);
+        // Aircraft transmitters and implausible skews are not a reference
+        for _ in 0..20 {
+            calibration.update(0, &frame(9.0, "Aircraft", GS));
+            calibration.update(0, &frame(80.0, GS, "Aircraft"));
+        }
+
+        calibration.end_session(&HashMap::from([(0, 0.5)]), true);
+        assert_eq!(calibration.correction(0), Some(-1.5));
+    }
+
+    #[test]
+    fn needs_enough_samples_and_apply() {
+        let mut calibration = Calibration::new();
+        learn(&mut calibration, 0, &[2.0; MIN_SAMPLES - 1]);
+        learn(&mut calibration, 1, &[2.0; MIN_SAMPLES]);
+        calibration.end_session(&HashMap::new(), false);
+        assert_eq!(calibration.correction(1), None);
+
+        learn(&mut calibration, 0, &[2.0; MIN_SAMPLES - 1]);
+        learn(&mut calibration, 1, &[-3.0; MIN_SAMPLES]);
+        calibration.end_session(&HashMap::new(), true);
+        assert_eq!(calibration.correction(0), None);
+        assert_eq!(calibration.correction(1), Some(3.0));
+
+        // Samples are used up by the evaluation that consumed them
+        calibration.end_session(&HashMap::from([(1, 3.0)]), true);
+        assert_eq!(calibration.correction(1), Some(3.0));
+    }
+}
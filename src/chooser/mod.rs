@@ -1,27 +1,149 @@
-use crate::config::FrequencyBandMap;
+use crate::config::{Config, FrequencyBandMap};
+use crate::events::Event;
 use serde_json::Value;
 use std::collections::HashMap;
+use std::time::{Duration, Instant};
 
 pub mod rotate;
+pub mod scan;
 pub mod single;
+pub mod timeout;
 pub mod tracker;
 
 pub trait ChooserPlugin {
-    fn choose<'a, 'b>(
+    fn choose(
         &mut self,
-        bands: &'a FrequencyBandMap,
-        props: &'b HashMap<&str, &str>,
-    ) -> Result<&'a Vec<u32>, String>;
+        bands: &FrequencyBandMap,
+        props: &HashMap<&str, &str>,
+    ) -> Result<u32, String>;
+
+    /// Chooses up to `count` distinct bands to listen to simultaneously, one
+    /// per receiver. The default picks bands one at a time with `choose`.
+    fn choose_set(
+        &mut self,
+        bands: &FrequencyBandMap,
+        props: &HashMap<&str, &str>,
+        count: usize,
+    ) -> Result<Vec<u32>, String> {
+        let mut remaining = bands.clone();
+        let mut chosen: Vec<u32> = vec![];
+
+        while chosen.len() < count && !remaining.is_empty() {
+            match self.choose(&remaining, props) {
+                Ok(band) => {
+                    remaining.remove(&band);
+                    chosen.push(band);
+                }
+                Err(e) if chosen.is_empty() => return Err(e),
+                Err(_) => break,
+            }
+        }
+
+        Ok(chosen)
+    }
 
     fn on_update(&mut self, frame: &Value) -> bool;
     fn on_timeout(&mut self) -> bool;
+
+    /// Called with every logon/logoff event derived from the received frames
+    fn on_event(&mut self, _event: &Event) {}
+
+    /// Inactivity timeout of a band being listened to, queried before waiting
+    /// on every frame; `timeout` is the configured timeout for the band
+    fn timeout(&self, _band: u32, timeout: Duration) -> Duration {
+        timeout
+    }
+
+    /// Point in time at which the current session should end regardless of
+    /// activity; `on_timeout` is invoked once it is reached
+    fn deadline(&self) -> Option<Instant> {
+        None
+    }
+}
+
+/// Props whose values list bands as `BAND;BAND`
+const BAND_PROPS: [&str; 2] = ["band", "start"];
+
+/// Replaces custom band names in band-valued props with the band keys they
+/// were assigned, so choosers only ever deal with numeric bands. Numbers in the
+/// custom band key space are rejected, custom bands are referred to by name.
+pub fn resolve_band_names(
+    props: &HashMap<&str, &str>,
+    names: &HashMap<String, u32>,
+) -> Result<HashMap<String, String>, String> {
+    props
+        .iter()
+        .map(|(key, value)| {
+            if !BAND_PROPS.contains(key) {
+                return Ok((key.to_string(), value.to_string()));
+            }
+
+            let entries = value
+                .split(';')
+                .map(|band| match Config::parse_band(band, names) {
+                    Some(band) => Ok(band.to_string()),
+                    None if band.parse::<u32>().is_ok() => Err(format!(
+                        "'{}' key refers to band {}, which is not a system table band",
+                        key, band
+                    )),
+                    // Left for the chooser to reject
+                    None => Ok(band.to_string()),
+                })
+                .collect::<Result<Vec<String>, String>>()?;
+
+            Ok((key.to_string(), entries.join(";")))
+        })
+        .collect()
+}
+
+/// Maps every frequency to all bands containing it. Custom bands reuse system
+/// table frequencies, so a frequency can belong to more than one band.
+pub fn freq_bands(bands: &FrequencyBandMap) -> HashMap<u32, Vec<u32>> {
+    let mut freq_bands: HashMap<u32, Vec<u32>> = HashMap::new();
+    for (band, freqs) in bands.iter() {
+        for freq in freqs.iter() {
+            freq_bands.entry(*freq).or_default().push(*band);
+        }
+    }
+    for bands in freq_bands.values_mut() {
+        bands.sort_unstable();
+    }
+
+    freq_bands
 }
 
 pub fn get(name: &str) -> Option<Box<dyn ChooserPlugin>> {
     match name {
         rotate::NAME => Some(Box::new(rotate::RotateChooserPlugin::new())),
+        scan::NAME => Some(Box::new(scan::ScanChooserPlugin::new())),
         single::NAME => Some(Box::new(single::SingleChooserPlugin::new())),
         tracker::NAME => Some(Box::new(tracker::TrackerChooserPlugin::new())),
         _ => None,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::CUSTOM_BAND_KEYS;
+
+    #[test]
+    fn band_props_resolve_names_and_reject_custom_keys() {
+        let names = HashMap::from([("pacific".to_string(), CUSTOM_BAND_KEYS)]);
+
+        let props = HashMap::from([("band", "pacific;13"), ("timeout", "60")]);
+        let resolved = resolve_band_names(&props, &names).unwrap();
+        assert_eq!(resolved["band"], format!("{};13", CUSTOM_BAND_KEYS));
+        assert_eq!(resolved["timeout"], "60");
+
+        let custom_key = CUSTOM_BAND_KEYS.to_string();
+        let props = HashMap::from([("start", custom_key.as_str())]);
+        assert!(resolve_band_names(&props, &names).is_err());
+
+        let props = HashMap::from([("band", "atlantic")]);
+        assert_eq!(
+            resolve_band_names(&props, &names).unwrap()["band"],
+            "atlantic"
+        );
+    }
+}
@@ -2,111 +2,184 @@ use log::*;
 use rand::Rng;
 use serde_json::Value;
 use std::collections::HashMap;
+use std::time::Duration;
 
+use crate::chooser::timeout::SessionTimeout;
 use crate::chooser::ChooserPlugin;
 use crate::config::FrequencyBandMap;
 
-pub const NAME: &'static str = "rotate";
+pub const NAME: &str = "rotate";
 
 const MAX_MEMORY_ENTRIES: usize = 8;
 
 pub struct RotateChooserPlugin {
-    recently_used: Vec<usize>,
-    band_idx: Option<usize>,
+    recently_used: Vec<u32>,
+    /// Position of the current band in the rotation
+    band: Option<(u32, u32)>,
+    session: SessionTimeout,
 }
 
 impl RotateChooserPlugin {
     pub fn new() -> Self {
         RotateChooserPlugin {
-            band_idx: None,
+            band: None,
             recently_used: vec![],
+            session: SessionTimeout::new(),
         }
     }
 }
 
+/// Bands rotate in order of their lowest frequency, so custom bands take
+/// their place among the system table bands
+fn position(bands: &FrequencyBandMap, band: u32) -> (u32, u32) {
+    (bands[&band].iter().min().copied().unwrap_or_default(), band)
+}
+
 impl ChooserPlugin for RotateChooserPlugin {
-    fn choose<'a, 'b>(
+    fn choose(
         &mut self,
-        bands: &'a FrequencyBandMap,
-        props: &'b HashMap<&str, &str>,
-    ) -> Result<&'a Vec<u32>, String> {
-        let mut band_keys: Vec<&u32> = bands.keys().into_iter().collect();
-        band_keys.sort_unstable();
+        bands: &FrequencyBandMap,
+        props: &HashMap<&str, &str>,
+    ) -> Result<u32, String> {
+        let mut band_keys: Vec<u32> = bands.keys().copied().collect();
+        band_keys.sort_unstable_by_key(|band| position(bands, *band));
 
         let switcher = *props.get("type").unwrap_or(&"inc");
-
-        if self.band_idx.is_none() {
-            let start: u32 = match props.get("start").unwrap_or(&"13").parse() {
-                Ok(start) => start,
-                Err(e) => {
-                    return Err(format!(
-                        "'start' key contains an invalid positive number: {}",
-                        e
-                    ))
+        self.session.configure(props)?;
+
+        // Bands are tracked by frequency rather than index since the set of
+        // available bands can change between sessions
+        let band = match self.band {
+            None => {
+                let start: u32 = match props.get("start").unwrap_or(&"13").parse() {
+                    Ok(start) => start,
+                    Err(e) => {
+                        return Err(format!(
+                            "'start' key contains an invalid positive number: {}",
+                            e
+                        ))
+                    }
+                };
+
+                if !band_keys.contains(&start) {
+                    return Err(format!("'start' key value ({}) is not a valid band", start));
                 }
-            };
-
-            self.band_idx = band_keys.iter().position(|&b| b == &start);
-            if self.band_idx.is_none() {
-                return Err(format!("'start' key value ({}) is not a valid band", start));
-            }
-
-            self.recently_used.push(self.band_idx.unwrap());
-        } else if switcher.eq("dec") {
-            info!("[dec]    current band_idx = {:?}", self.band_idx);
 
-            if self.band_idx.unwrap() == 0 {
-                self.band_idx = Some(band_keys.len() - 1);
-            } else {
-                self.band_idx = Some(self.band_idx.unwrap() - 1);
+                self.recently_used.push(start);
+                start
             }
-
-            info!("[dec]    next band_idx = {:?}", self.band_idx);
-        } else if switcher.eq("random") {
-            let mut new_idx = self.band_idx.unwrap();
-            info!(
-                "[random] current band_idx = {:?}, recently_used = {:?}",
-                self.band_idx, self.recently_used
-            );
-
-            while self
-                .recently_used
-                .iter()
-                .position(|&b| b == new_idx)
-                .is_some()
-            {
-                new_idx = rand::thread_rng().gen_range(0..(band_keys.len() - 1))
-            }
-
-            if self.recently_used.len() == MAX_MEMORY_ENTRIES {
-                self.recently_used.remove(0);
+            Some((lowest, current)) if switcher.eq("dec") => {
+                info!("[dec]    current band = {}", current);
+
+                let band = match band_keys
+                    .iter()
+                    .rev()
+                    .find(|&&b| position(bands, b) < (lowest, current))
+                {
+                    Some(band) => *band,
+                    None => *band_keys.last().ok_or("No bands available")?,
+                };
+
+                info!("[dec]    next band = {}", band);
+                band
             }
+            Some((_, current)) if switcher.eq("random") => {
+                info!(
+                    "[random] current band = {}, recently_used = {:?}",
+                    current, self.recently_used
+                );
+
+                let mut candidates: Vec<u32> = band_keys
+                    .iter()
+                    .filter(|b| !self.recently_used.contains(b))
+                    .copied()
+                    .collect();
+                if candidates.is_empty() {
+                    candidates = band_keys
+                        .iter()
+                        .filter(|&&b| b != current)
+                        .copied()
+                        .collect();
+                }
+                if candidates.is_empty() {
+                    candidates = band_keys.clone();
+                }
+                if candidates.is_empty() {
+                    return Err("No bands available".to_string());
+                }
 
-            self.recently_used.push(new_idx);
-            self.band_idx = Some(new_idx);
+                let band = candidates[rand::thread_rng().gen_range(0..candidates.len())];
 
-            info!("[random] next band_idx = {:?}", self.band_idx);
-        } else {
-            info!("[inc]    current band_idx = {:?}", self.band_idx);
+                if self.recently_used.len() == MAX_MEMORY_ENTRIES {
+                    self.recently_used.remove(0);
+                }
+                self.recently_used.push(band);
 
-            if self.band_idx.unwrap() + 1 >= band_keys.len() {
-                self.band_idx = Some(0);
-            } else {
-                self.band_idx = Some(self.band_idx.unwrap() + 1);
+                info!("[random] next band = {}", band);
+                band
+            }
+            Some((lowest, current)) => {
+                info!("[inc]    current band = {}", current);
+
+                let band = match band_keys
+                    .iter()
+                    .find(|&&b| position(bands, b) > (lowest, current))
+                {
+                    Some(band) => *band,
+                    None => *band_keys.first().ok_or("No bands available")?,
+                };
+
+                info!("[inc]    next band = {}", band);
+                band
             }
+        };
 
-            info!("[inc]    next band_idx = {:?}", self.band_idx);
-        }
+        self.band = Some(position(bands, band));
+        self.session.start(band, &bands[&band]);
 
-        let band = band_keys[self.band_idx.unwrap()];
-        bands.get(&band).ok_or(format!("Invalid band: {}", band))
+        Ok(band)
     }
 
-    fn on_update(&mut self, _frame: &Value) -> bool {
+    fn on_update(&mut self, frame: &Value) -> bool {
+        self.session.on_frame(frame);
         false
     }
 
     fn on_timeout(&mut self) -> bool {
         true
     }
+
+    fn timeout(&self, band: u32, timeout: Duration) -> Duration {
+        self.session.timeout(band, timeout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::CUSTOM_BAND_KEYS;
+
+    fn bands() -> FrequencyBandMap {
+        FrequencyBandMap::from([
+            (8, vec![8912, 8927]),
+            (13, vec![13276, 13312]),
+            (21, vec![21934]),
+            (CUSTOM_BAND_KEYS, vec![11184, 11384]),
+        ])
+    }
+
+    fn rotation(switcher: &str, count: usize) -> Vec<u32> {
+        let mut plugin = RotateChooserPlugin::new();
+        let props = HashMap::from([("type", switcher), ("start", "8")]);
+        (0..count)
+            .map(|_| plugin.choose(&bands(), &props).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn custom_bands_rotate_in_frequency_order() {
+        let custom = CUSTOM_BAND_KEYS;
+        assert_eq!(rotation("inc", 5), vec![8, custom, 13, 21, 8]);
+        assert_eq!(rotation("dec", 5), vec![8, 21, 13, custom, 8]);
+    }
 }
@@ -0,0 +1,352 @@
+use log::*;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crate::chooser::{self, ChooserPlugin};
+use crate::config::FrequencyBandMap;
+use crate::events::{Event, EventKind};
+
+pub const NAME: &str = "scan";
+
+const SPDU_WEIGHT: f64 = 2.0;
+
+/// Extra score for aircraft logging on, a sign of a band in active use
+const LOGON_WEIGHT: f64 = 3.0;
+
+enum Phase {
+    Scanning(Vec<u32>),
+    Settled,
+}
+
+pub struct ScanChooserPlugin {
+    dwell: u64,
+    settle: u64,
+
+    phase: Phase,
+    scores: HashMap<u32, f64>,
+    freq_bands: HashMap<u32, Vec<u32>>,
+
+    current_bands: Vec<u32>,
+    session_started: Option<Instant>,
+    deadline: Option<Instant>,
+}
+
+impl ScanChooserPlugin {
+    pub fn new() -> Self {
+        ScanChooserPlugin {
+            dwell: 0,
+            settle: 0,
+            phase: Phase::Scanning(vec![]),
+            scores: HashMap::new(),
+            freq_bands: HashMap::new(),
+            current_bands: vec![],
+            session_started: None,
+            deadline: None,
+        }
+    }
+
+    fn parse_secs(props: &HashMap<&str, &str>, key: &str, default: &str) -> Result<u64, String> {
+        match props.get(key).unwrap_or(&default).parse() {
+            Ok(secs) if secs > 0 => Ok(secs),
+            Ok(_) => Err(format!("'{}' must be greater than zero", key)),
+            Err(e) => Err(format!("'{}' is not a valid positive number: {}", key, e)),
+        }
+    }
+
+    /// Converts the raw activity scores gathered on the current bands into
+    /// per-minute rates so sessions cut short by inactivity compare fairly
+    fn record_scores(&mut self) {
+        if let (Phase::Scanning(_), Some(started)) = (&self.phase, self.session_started) {
+            let minutes = (started.elapsed().as_secs_f64() / 60.0).max(1.0 / 60.0);
+            for band in self.current_bands.iter() {
+                if let Some(score) = self.scores.get_mut(band) {
+                    *score /= minutes;
+                    info!("[scan]   band {} activity = {:.2}/min", band, score);
+                }
+            }
+        }
+    }
+
+    fn best_bands(&self, bands: &FrequencyBandMap, count: usize) -> Vec<u32> {
+        let mut ranked: Vec<(u32, f64)> = self
+            .scores
+            .iter()
+            .filter(|(band, score)| **score > 0.0 && bands.contains_key(band))
+            .map(|(band, score)| (*band, *score))
+            .collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+
+        ranked
+            .into_iter()
+            .take(count)
+            .map(|(band, _)| band)
+            .collect()
+    }
+}
+
+impl ChooserPlugin for ScanChooserPlugin {
+    fn choose(
+        &mut self,
+        bands: &FrequencyBandMap,
+        props: &HashMap<&str, &str>,
+    ) -> Result<u32, String> {
+        let chosen = self.choose_set(bands, props, 1)?;
+        chosen
+            .first()
+            .copied()
+            .ok_or("No bands available".to_string())
+    }
+
+    fn choose_set(
+        &mut self,
+        bands: &FrequencyBandMap,
+        props: &HashMap<&str, &str>,
+        count: usize,
+    ) -> Result<Vec<u32>, String> {
+        if self.dwell == 0 {
+            self.dwell = Self::parse_secs(props, "dwell", "30")?;
+            self.settle = Self::parse_secs(props, "settle", "1800")?;
+            self.freq_bands = chooser::freq_bands(bands);
+        }
+
+        self.record_scores();
+
+        if let Phase::Scanning(queue) = &mut self.phase {
+            queue.retain(|band| bands.contains_key(band));
+        }
+
+        // A survey that heard nothing starts over straight away
+        let next_bands: Vec<u32> = loop {
+            match &mut self.phase {
+                Phase::Scanning(queue) if !queue.is_empty() => {
+                    break queue.drain(..count.min(queue.len())).collect();
+                }
+                Phase::Scanning(_) if !self.scores.is_empty() => {
+                    let best = self.best_bands(bands, count);
+                    if best.is_empty() {
+                        info!("[scan]   survey found no activity, rescanning...");
+                        self.scores.clear();
+                        continue;
+                    }
+
+                    info!("[scan]   survey complete, settling on bands {:?}", best);
+                    self.phase = Phase::Settled;
+                    break best;
+                }
+                _ => {
+                    let mut band_keys: Vec<u32> = bands.keys().copied().collect();
+                    band_keys.sort_unstable();
+
+                    if band_keys.is_empty() {
+                        return Err("No bands available".to_string());
+                    }
+
+                    info!("[scan]   starting survey of bands {:?}", band_keys);
+                    self.scores.clear();
+
+                    let next_bands = band_keys.drain(..count.min(band_keys.len())).collect();
+                    self.phase = Phase::Scanning(band_keys);
+                    break next_bands;
+                }
+            }
+        };
+
+        let secs = match self.phase {
+            Phase::Scanning(_) => {
+                for band in next_bands.iter() {
+                    self.scores.insert(*band, 0.0);
+                }
+                self.dwell
+            }
+            Phase::Settled => self.settle,
+        };
+
+        let now = Instant::now();
+        self.current_bands = next_bands.clone();
+        self.session_started = Some(now);
+        self.deadline = Some(now + Duration::from_secs(secs));
+
+        Ok(next_bands)
+    }
+
+    fn on_update(&mut self, frame: &Value) -> bool {
+        if let Phase::Settled = self.phase {
+            return false;
+        }
+
+        let hfdl = frame.get("hfdl");
+        let bands = match hfdl
+            .and_then(|hfdl| hfdl.get("freq"))
+            .and_then(|freq| freq.as_u64())
+            .and_then(|freq| self.freq_bands.get(&((freq / 1000) as u32)))
+        {
+            Some(bands) => bands,
+            None => return false,
+        };
+        let weight = match hfdl.and_then(|hfdl| hfdl.get("spdu")) {
+            Some(_) => SPDU_WEIGHT,
+            None => 1.0,
+        };
+
+        for band in bands.iter().filter(|b| self.current_bands.contains(b)) {
+            *self.scores.entry(*band).or_insert(0.0) += weight;
+        }
+
+        false
+    }
+
+    fn on_timeout(&mut self) -> bool {
+        true
+    }
+
+    fn on_event(&mut self, event: &Event) {
+        if let Phase::Settled = self.phase {
+            return;
+        }
+        if !matches!(event.event, EventKind::Logon | EventKind::LogonResume) {
+            return;
+        }
+
+        for band in self.freq_bands.get(&event.freq).into_iter().flatten() {
+            if self.current_bands.contains(band) {
+                *self.scores.entry(*band).or_insert(0.0) += LOGON_WEIGHT;
+            }
+        }
+    }
+
+    fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn bands() -> FrequencyBandMap {
+        HashMap::from([
+            (8, vec![8912, 8927]),
+            (13, vec![13276, 13312]),
+            (21, vec![21934]),
+        ])
+    }
+
+    fn props() -> HashMap<&'static str, &'static str> {
+        HashMap::from([("dwell", "30"), ("settle", "1800")])
+    }
+
+    fn lpdu(khz: u32) -> Value {
+        json!({"hfdl": {"freq": khz * 1000, "lpdu": {}}})
+    }
+
+    fn spdu(khz: u32) -> Value {
+        json!({"hfdl": {"freq": khz * 1000, "spdu": {}}})
+    }
+
+    fn secs_left(scan: &ScanChooserPlugin) -> u64 {
+        scan.deadline()
+            .unwrap()
+            .saturating_duration_since(Instant::now())
+            .as_secs()
+    }
+
+    fn logon(khz: u32) -> Event {
+        Event {
+            ts: 0,
+            event: EventKind::Logon,
+            gs: 7,
+            gs_name: None,
+            freq: khz,
+            ac_id: Some(23),
+            icao: None,
+        }
+    }
+
+    #[test]
+    fn dwells_on_every_band_then_settles_on_the_busiest() {
+        let (bands, props) = (bands(), props());
+        let mut scan = ScanChooserPlugin::new();
+
+        assert_eq!(scan.choose(&bands, &props), Ok(8));
+        assert!((25..=30).contains(&secs_left(&scan)));
+        scan.on_update(&lpdu(8927));
+
+        assert_eq!(scan.choose(&bands, &props), Ok(13));
+        // Frames from other bands are not credited to the current one
+        scan.on_update(&spdu(8927));
+
+        assert_eq!(scan.choose(&bands, &props), Ok(21));
+        scan.on_update(&spdu(21934));
+
+        assert_eq!(scan.choose(&bands, &props), Ok(21));
+        assert!(matches!(scan.phase, Phase::Settled));
+        assert!(secs_left(&scan) > 1700);
+
+        // Settled sessions do not score, and the next choice starts a new survey
+        scan.on_update(&lpdu(21934));
+        assert_eq!(scan.choose(&bands, &props), Ok(8));
+        assert!(matches!(scan.phase, Phase::Scanning(ref queue) if queue == &[13, 21]));
+    }
+
+    #[test]
+    fn survey_scores_weigh_squitters_and_logons() {
+        let (bands, props) = (bands(), props());
+        let mut scan = ScanChooserPlugin::new();
+
+        assert_eq!(scan.choose_set(&bands, &props, 2), Ok(vec![8, 13]));
+        scan.on_update(&spdu(8912));
+        scan.on_update(&lpdu(13276));
+        scan.on_event(&logon(13312));
+
+        assert_eq!(scan.choose_set(&bands, &props, 2), Ok(vec![21]));
+        scan.on_update(&lpdu(21934));
+        scan.on_update(&lpdu(21934));
+        scan.on_update(&lpdu(21934));
+
+        // 13 scored 1 + 3, 21 scored 3 and 8 scored 2
+        assert_eq!(scan.choose_set(&bands, &props, 2), Ok(vec![13, 21]));
+    }
+
+    #[test]
+    fn overlapping_bands_are_both_credited() {
+        let mut bands = bands();
+        bands.insert(13276, vec![13276, 13312]);
+        let props = props();
+        let mut scan = ScanChooserPlugin::new();
+
+        assert_eq!(
+            scan.choose_set(&bands, &props, 4),
+            Ok(vec![8, 13, 21, 13276])
+        );
+        scan.on_update(&lpdu(13276));
+
+        assert_eq!(scan.choose_set(&bands, &props, 2), Ok(vec![13, 13276]));
+    }
+
+    #[test]
+    fn silent_survey_starts_over() {
+        let (bands, props) = (bands(), props());
+        let mut scan = ScanChooserPlugin::new();
+
+        assert_eq!(scan.choose_set(&bands, &props, 3), Ok(vec![8, 13, 21]));
+        assert_eq!(scan.choose_set(&bands, &props, 3), Ok(vec![8, 13, 21]));
+        assert!(matches!(scan.phase, Phase::Scanning(ref queue) if queue.is_empty()));
+        assert!((25..=30).contains(&secs_left(&scan)));
+    }
+
+    #[test]
+    fn rejects_invalid_durations() {
+        let bands = bands();
+        let mut scan = ScanChooserPlugin::new();
+        assert!(scan
+            .choose(&bands, &HashMap::from([("dwell", "0")]))
+            .is_err());
+
+        let mut scan = ScanChooserPlugin::new();
+        assert!(scan
+            .choose(&bands, &HashMap::from([("settle", "x")]))
+            .is_err());
+    }
+}
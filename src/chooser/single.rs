@@ -5,7 +5,7 @@ use serde_json::Value;
 use crate::chooser::ChooserPlugin;
 use crate::config::FrequencyBandMap;
 
-pub const NAME: &'static str = "single";
+pub const NAME: &str = "single";
 
 pub struct SingleChooserPlugin {}
 
@@ -16,26 +16,35 @@ impl SingleChooserPlugin {
 }
 
 impl ChooserPlugin for SingleChooserPlugin {
-    fn choose<'a, 'b>(
+    fn choose(
         &mut self,
-        bands: &'a FrequencyBandMap,
-        props: &'b HashMap<&str, &str>,
-    ) -> Result<&'a Vec<u32>, String> {
+        bands: &FrequencyBandMap,
+        props: &HashMap<&str, &str>,
+    ) -> Result<u32, String> {
         if !props.contains_key("band") {
             return Err("Missing 'band' key in props".to_string());
         }
 
-        let band: u32 = match props.get("band").unwrap().parse() {
-            Ok(band) => band,
-            Err(e) => {
-                return Err(format!(
-                    "'band' key contains an invalid positive number: {}",
-                    e
-                ))
+        // Multiple bands may be listed for setups with several receivers
+        let mut last_band = 0;
+        for band in props.get("band").unwrap().split(';') {
+            let band: u32 = match band.parse() {
+                Ok(band) => band,
+                Err(e) => {
+                    return Err(format!(
+                        "'band' key contains an invalid positive number: {}",
+                        e
+                    ))
+                }
+            };
+
+            if bands.contains_key(&band) {
+                return Ok(band);
             }
-        };
+            last_band = band;
+        }
 
-        bands.get(&band).ok_or(format!("Invalid band: {}", band))
+        Err(format!("Invalid band: {}", last_band))
     }
 
     fn on_update(&mut self, _frame: &Value) -> bool {
@@ -0,0 +1,181 @@
+use log::*;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+const BUSY_FRAMES_PER_MIN: f64 = 10.0;
+
+/// Traffic heard on one band since it was last chosen
+struct BandActivity {
+    freqs: Vec<u32>,
+    frames: u32,
+    started: Instant,
+}
+
+/// Inactivity timeout bookkeeping shared by choosers that rotate on timeout.
+/// Per-band timeouts themselves come from `--band-timeout`.
+///
+/// Recognized props:
+/// * `adaptive` - doubles a band's timeout while its traffic is heavy and
+///   halves it while nothing has been heard on it
+pub struct SessionTimeout {
+    adaptive: bool,
+
+    /// Keyed by band, so receivers listening to different bands at the same
+    /// time are judged on their own traffic
+    bands: HashMap<u32, BandActivity>,
+}
+
+impl SessionTimeout {
+    pub fn new() -> Self {
+        SessionTimeout {
+            adaptive: false,
+            bands: HashMap::new(),
+        }
+    }
+
+    pub fn configure(&mut self, props: &HashMap<&str, &str>) -> Result<(), String> {
+        if props.contains_key("timeouts") {
+            return Err(
+                "'timeouts' is no longer a chooser prop, use --band-timeout BAND=SECONDS"
+                    .to_string(),
+            );
+        }
+
+        self.adaptive = match props.get("adaptive") {
+            Some(&"") | Some(&"true") | Some(&"1") => true,
+            Some(&"false") | Some(&"0") | None => false,
+            Some(val) => return Err(format!("'adaptive' is not a valid boolean: {}", val)),
+        };
+
+        Ok(())
+    }
+
+    /// Starts counting traffic on `band`, made of `freqs` (kHz)
+    pub fn start(&mut self, band: u32, freqs: &[u32]) {
+        self.bands.insert(
+            band,
+            BandActivity {
+                freqs: freqs.to_vec(),
+                frames: 0,
+                started: Instant::now(),
+            },
+        );
+    }
+
+    /// Credits a frame to every band containing its frequency
+    pub fn on_frame(&mut self, frame: &Value) {
+        let freq = match frame.pointer("/hfdl/freq").and_then(Value::as_u64) {
+            Some(freq) => (freq / 1000) as u32,
+            None => return,
+        };
+
+        for activity in self.bands.values_mut() {
+            if activity.freqs.contains(&freq) {
+                activity.frames += 1;
+            }
+        }
+    }
+
+    /// Inactivity timeout of `band`, given its configured `timeout`
+    pub fn timeout(&self, band: u32, timeout: Duration) -> Duration {
+        if !self.adaptive {
+            return timeout;
+        }
+
+        let activity = match self.bands.get(&band) {
+            Some(activity) => activity,
+            None => return timeout,
+        };
+        if activity.frames == 0 {
+            return timeout / 2;
+        }
+
+        let minutes = (activity.started.elapsed().as_secs_f64() / 60.0).max(1.0);
+        let rate = activity.frames as f64 / minutes;
+        if rate >= BUSY_FRAMES_PER_MIN {
+            debug!(
+                "Heavy traffic on band {} ({:.1} frames/min), extending timeout",
+                band, rate
+            );
+            return timeout * 2;
+        }
+
+        timeout
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const TIMEOUT: Duration = Duration::from_secs(300);
+
+    fn frame(khz: u32) -> Value {
+        json!({"hfdl": {"freq": khz * 1000}})
+    }
+
+    fn adaptive() -> SessionTimeout {
+        let mut session = SessionTimeout::new();
+        session
+            .configure(&HashMap::from([("adaptive", "")]))
+            .unwrap();
+        session.start(13, &[13276, 13312]);
+        session.start(21, &[21934]);
+        session
+    }
+
+    #[test]
+    fn configure_props() {
+        let mut session = SessionTimeout::new();
+        assert!(session.configure(&HashMap::new()).is_ok());
+        assert!(!session.adaptive);
+        assert!(session
+            .configure(&HashMap::from([("adaptive", "true")]))
+            .is_ok());
+        assert!(session.adaptive);
+
+        assert!(session
+            .configure(&HashMap::from([("adaptive", "maybe")]))
+            .is_err());
+        assert!(session
+            .configure(&HashMap::from([("timeouts", "13:300")]))
+            .is_err());
+    }
+
+    #[test]
+    fn fixed_timeout_unless_adaptive() {
+        let mut session = SessionTimeout::new();
+        session.start(13, &[13276]);
+        assert_eq!(session.timeout(13, TIMEOUT), TIMEOUT);
+    }
+
+    #[test]
+    fn adaptive_timeout_follows_each_bands_own_traffic() {
+        let mut session = adaptive();
+        for _ in 0..10 {
+            session.on_frame(&frame(13276));
+        }
+
+        assert_eq!(session.timeout(13, TIMEOUT), TIMEOUT * 2);
+        assert_eq!(session.timeout(21, TIMEOUT), TIMEOUT / 2);
+
+        session.on_frame(&frame(21934));
+        assert_eq!(session.timeout(21, TIMEOUT), TIMEOUT);
+
+        // Choosing a band again starts counting from scratch
+        session.start(13, &[13276, 13312]);
+        assert_eq!(session.timeout(13, TIMEOUT), TIMEOUT / 2);
+    }
+
+    #[test]
+    fn unknown_bands_and_frequencies_are_ignored() {
+        let mut session = adaptive();
+        session.on_frame(&frame(8927));
+        session.on_frame(&json!({"hfdl": {}}));
+
+        assert_eq!(session.timeout(13, TIMEOUT), TIMEOUT / 2);
+        assert_eq!(session.timeout(8, TIMEOUT), TIMEOUT);
+    }
+}
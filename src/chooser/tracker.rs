@@ -1,84 +1,103 @@
 use log::*;
 use std::collections::HashMap;
-use std::time::Instant;
+use std::fmt;
+use std::time::{Duration, Instant};
 
 use rand::seq::SliceRandom;
-use serde::Deserialize;
 use serde_json::Value;
 
-use crate::chooser::ChooserPlugin;
+use crate::chooser::timeout::SessionTimeout;
+use crate::chooser::{self, ChooserPlugin};
 use crate::config::FrequencyBandMap;
+use crate::frame::{Entity, MessageFrame};
 
-pub const NAME: &'static str = "tracker";
+pub const NAME: &str = "tracker";
 pub const MAX_VISITED_ENTRIES: usize = 6;
 
-#[allow(dead_code)]
-#[derive(Deserialize, Debug)]
-struct Frequency {
-    id: u8,
-    freq: f64,
+enum TargetMatch {
+    Name(String),
+    Id(u8),
 }
 
-#[allow(dead_code)]
-#[derive(Deserialize, Debug)]
-struct Entity {
-    id: u8,
+struct Target {
+    matcher: TargetMatch,
+    priority: u32,
 
-    #[serde(alias = "type")]
-    entity_type: String,
-
-    #[serde(alias = "name")]
-    entity_name: Option<String>,
+    bands: Vec<u32>,
+    bands_last_updated: Option<Instant>,
+    last_heard: Option<Instant>,
 }
 
-#[allow(dead_code)]
-#[derive(Deserialize, Debug)]
-struct GroundStation {
-    gs: Entity,
-    utc_sync: bool,
-    freqs: Vec<Frequency>,
-}
+impl Target {
+    /// Parses a target specification of the form `NAME_OR_ID[:PRIORITY]`
+    fn parse(spec: &str) -> Result<Target, String> {
+        let (ident, priority) = match spec.find(':') {
+            Some(delim) => (&spec[..delim], &spec[(delim + 1)..]),
+            None => (spec, "1"),
+        };
 
-#[allow(dead_code)]
-#[derive(Deserialize, Debug)]
-struct LPDU {
-    err: bool,
-    src: Entity,
-    dst: Entity,
-}
+        if ident.is_empty() {
+            return Err(format!("Target '{}' is missing a name or id", spec));
+        }
 
-#[allow(dead_code)]
-#[derive(Deserialize, Debug)]
-struct SPDU {
-    err: bool,
-    src: Entity,
-    gs_status: Vec<GroundStation>,
-}
+        let priority: u32 = match priority.parse() {
+            Ok(priority) if priority > 0 => priority,
+            _ => {
+                return Err(format!(
+                    "Target '{}' has an invalid priority: '{}'",
+                    ident, priority
+                ))
+            }
+        };
+
+        let matcher = match ident.parse() {
+            Ok(id) => TargetMatch::Id(id),
+            Err(_) => TargetMatch::Name(ident.to_string()),
+        };
 
-#[derive(Deserialize, Debug)]
-struct HFDL {
-    freq: u32,
-    spdu: Option<SPDU>,
-    lpdu: Option<LPDU>,
+        Ok(Target {
+            matcher,
+            priority,
+            bands: vec![],
+            bands_last_updated: None,
+            last_heard: None,
+        })
+    }
+
+    fn matches(&self, entity: &Entity) -> bool {
+        match &self.matcher {
+            TargetMatch::Name(name) => entity
+                .entity_name
+                .as_ref()
+                .is_some_and(|n| n.starts_with(name)),
+            TargetMatch::Id(id) => entity.is_ground_station() && entity.id == *id,
+        }
+    }
 }
 
-#[derive(Deserialize, Debug)]
-struct MessageFrame {
-    hfdl: HFDL,
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.matcher {
+            TargetMatch::Name(name) => write!(f, "{}", name)?,
+            TargetMatch::Id(id) => write!(f, "#{}", id)?,
+        }
+        write!(f, " (priority={})", self.priority)
+    }
 }
 
 pub struct TrackerChooserPlugin {
     recently_visited: Vec<u32>,
 
-    target: Option<String>,
-    target_bands: Vec<u32>,
-    target_bands_last_updated: Option<Instant>,
+    targets: Vec<Target>,
+    freq_bands: HashMap<u32, Vec<u32>>,
 
     current_band: Option<u32>,
     next_band: Option<u32>,
 
     last_heard_timeout: u64,
     gs_last_heard: Option<Instant>,
+
+    session: SessionTimeout,
 }
 
 impl TrackerChooserPlugin {
@@ -86,57 +105,92 @@ impl TrackerChooserPlugin {
         TrackerChooserPlugin {
             recently_visited: vec![],
             gs_last_heard: None,
-            target: None,
-            target_bands: vec![],
-            target_bands_last_updated: None,
+            targets: vec![],
+            freq_bands: HashMap::new(),
             current_band: None,
             next_band: None,
             last_heard_timeout: 0,
+            session: SessionTimeout::new(),
+        }
+    }
+
+    fn mark_heard(&mut self, entity: &Entity, freq: u32, direction: &str) {
+        for target in self.targets.iter_mut().filter(|t| t.matches(entity)) {
+            info!("Received {} on {} target GS: {}", direction, freq, target);
+
+            let now = Instant::now();
+            target.last_heard = Some(now);
+            self.gs_last_heard = Some(now);
         }
     }
 
     fn determine_next_band(&mut self) {
         let mut rng = rand::thread_rng();
-
-        if !self.target_bands.is_empty() {
-            if self.target_bands_last_updated.is_some()
-                && self.target_bands_last_updated.unwrap().elapsed().as_secs()
-                    < self.last_heard_timeout * 2
-            {
-                info!("Recent SPDU containing target GS still fresh...");
-
-                let mut candidates: Vec<u32> = self
-                    .target_bands
-                    .clone()
-                    .into_iter()
-                    .filter(|b| b != &self.current_band.unwrap())
-                    .collect();
-                if candidates.len() > 0 {
-                    candidates.shuffle(&mut rng);
-                    self.next_band = Some(candidates[0]);
-                    info!("Selecting next band: {}", candidates[0]);
-                } else {
-                    info!("No new bands discovered, ignored.");
+        let fresh_secs = self.last_heard_timeout * 2;
+
+        // Score every band by the summed priority of the targets currently
+        // transmitting on it so the band covering the most important targets wins
+        let mut scores: HashMap<u32, u32> = HashMap::new();
+        for target in self.targets.iter_mut() {
+            match target.bands_last_updated {
+                Some(updated) if updated.elapsed().as_secs() < fresh_secs => {
+                    for band in target.bands.iter() {
+                        *scores.entry(*band).or_insert(0) += target.priority;
+                    }
+                }
+                Some(_) => {
+                    info!("Recent SPDU containing target GS {} is stale.", target);
+                    target.bands.clear();
+                    target.bands_last_updated = None;
                 }
-            } else {
-                info!("Recent SPDU is stale.");
-                self.target_bands_last_updated = None;
+                None => {}
             }
         }
+
+        if let Some(current_band) = self.current_band {
+            scores.remove(&current_band);
+        }
+
+        let best_score = match scores.values().max() {
+            Some(score) => *score,
+            None => {
+                info!("No new bands discovered, ignored.");
+                return;
+            }
+        };
+
+        let candidates: Vec<u32> = scores
+            .into_iter()
+            .filter(|(_, score)| *score == best_score)
+            .map(|(band, _)| band)
+            .collect();
+
+        let band = *candidates.choose(&mut rng).unwrap();
+        self.next_band = Some(band);
+        info!("Selecting next band: {} (score={})", band, best_score);
     }
 }
 
 impl ChooserPlugin for TrackerChooserPlugin {
-    fn choose<'a, 'b>(
+    fn choose(
         &mut self,
-        bands: &'a FrequencyBandMap,
-        props: &'b HashMap<&str, &str>,
-    ) -> Result<&'a Vec<u32>, String> {
-        if self.target.is_none() {
-            self.target = props.get("target").map(|s| s.to_string());
-            if self.target.is_none() {
+        bands: &FrequencyBandMap,
+        props: &HashMap<&str, &str>,
+    ) -> Result<u32, String> {
+        if self.targets.is_empty() {
+            let specs = match props.get("target") {
+                Some(specs) => specs,
+                None => return Err("No target specified".to_string()),
+            };
+
+            for spec in specs.split(';').filter(|s| !s.is_empty()) {
+                self.targets.push(Target::parse(spec)?);
+            }
+            if self.targets.is_empty() {
                 return Err("No target specified".to_string());
             }
+
+            self.freq_bands = chooser::freq_bands(bands);
         }
 
         if self.last_heard_timeout == 0 {
@@ -146,100 +200,82 @@ impl ChooserPlugin for TrackerChooserPlugin {
             };
         }
 
-        let next_band: u32;
+        self.session.configure(props)?;
 
-        if self.next_band.is_none() {
-            let mut rng = rand::thread_rng();
-            let mut band_keys: Vec<&u32> = bands.keys().into_iter().collect();
-            band_keys.shuffle(&mut rng);
-
-            while !self.recently_visited.is_empty()
-                && self
-                    .recently_visited
+        let next_band = match self.next_band.take() {
+            Some(band) if bands.contains_key(&band) => band,
+            _ => {
+                let mut rng = rand::thread_rng();
+                let band_keys: Vec<u32> = bands.keys().copied().collect();
+                let unvisited: Vec<u32> = band_keys
                     .iter()
-                    .position(|&b| b == *band_keys[0])
-                    .is_some()
-            {
-                band_keys.remove(0);
-            }
+                    .filter(|b| !self.recently_visited.contains(b))
+                    .copied()
+                    .collect();
 
-            next_band = *band_keys[0];
-        } else {
-            next_band = self.next_band.unwrap();
-            self.next_band = None;
-        }
+                match unvisited.choose(&mut rng).or(band_keys.choose(&mut rng)) {
+                    Some(band) => *band,
+                    None => return Err("No bands available".to_string()),
+                }
+            }
+        };
 
         if self.recently_visited.len() == MAX_VISITED_ENTRIES {
             self.recently_visited.remove(0);
         }
         self.recently_visited.push(next_band);
         self.current_band = Some(next_band);
+        self.session.start(next_band, &bands[&next_band]);
 
-        bands
-            .get(&next_band)
-            .ok_or(format!("Invalid band: {}", next_band))
+        Ok(next_band)
     }
 
     fn on_update(&mut self, frame: &Value) -> bool {
-        let target = self.target.as_ref().unwrap();
+        self.session.on_frame(frame);
 
-        let msg: MessageFrame = match serde_json::from_value(frame.clone()) {
+        let msg = match MessageFrame::from_value(frame) {
             Ok(m) => m,
             Err(e) => {
-                error!("Failed to coerce frame into MessageFrame: {}", e);
+                error!("{}", e);
                 return false;
             }
         };
 
-        let freq = msg.hfdl.freq / 1000;
+        let freq = msg.hfdl.freq_khz();
         let mut spdu_contains_target = false;
 
-        if msg.hfdl.spdu.is_some() {
-            let spdu = msg.hfdl.spdu.unwrap();
-
-            let src_entity = spdu.src.entity_name;
-            if src_entity.is_some() && src_entity.unwrap().starts_with(target) {
-                info!("Received SPDU on {} from target GS: {}", freq, target);
-                self.gs_last_heard = Some(Instant::now());
-            }
+        if let Some(spdu) = msg.hfdl.spdu {
+            self.mark_heard(&spdu.src, freq, "SPDU from");
 
             for station in spdu.gs_status.iter() {
-                if station.gs.entity_name.is_some()
-                    && station.gs.entity_name.as_ref().unwrap().starts_with(target)
-                {
-                    self.target_bands.clear();
-                    self.target_bands.extend_from_slice(
-                        station
-                            .freqs
-                            .iter()
-                            .map(|f| (f.freq / 1000.0) as u32)
-                            .collect::<Vec<u32>>()
-                            .as_slice(),
-                    );
-                    self.target_bands_last_updated = Some(Instant::now());
+                for target in self.targets.iter_mut() {
+                    if !target.matches(&station.gs) {
+                        continue;
+                    }
+
+                    let mut target_bands: Vec<u32> = station
+                        .freqs
+                        .iter()
+                        .filter_map(|f| self.freq_bands.get(&f.khz()))
+                        .flatten()
+                        .copied()
+                        .collect();
+                    target_bands.sort_unstable();
+                    target_bands.dedup();
+
+                    target.bands = target_bands;
+                    target.bands_last_updated = Some(Instant::now());
 
                     info!(
-                        "Found SPDU containing target GS freqs: {:?}",
-                        self.target_bands
+                        "Found SPDU containing target GS {} bands: {:?}",
+                        target, target.bands
                     );
                     spdu_contains_target = true;
-                    break;
                 }
             }
-        } else if msg.hfdl.lpdu.is_some() {
-            let lpdu = msg.hfdl.lpdu.unwrap();
-
-            let src_entity = lpdu.src.entity_name;
-            if src_entity.is_some() && src_entity.unwrap().starts_with(target) {
-                info!("Received LPDU on {} from target GS: {}", freq, target);
-                self.gs_last_heard = Some(Instant::now());
-            }
-
-            let dst_entity = lpdu.dst.entity_name;
-            if dst_entity.is_some() && dst_entity.unwrap().starts_with(target) {
-                info!("Received LPDU on {} to target GS: {}", freq, target);
-                self.gs_last_heard = Some(Instant::now());
-            }
+        } else if let Some(lpdu) = msg.hfdl.lpdu {
+            self.mark_heard(&lpdu.src, freq, "LPDU from");
+            self.mark_heard(&lpdu.dst, freq, "LPDU to");
         }
 
         if let Some(timer) = self.gs_last_heard {
@@ -247,18 +283,19 @@ impl ChooserPlugin for TrackerChooserPlugin {
                 self.gs_last_heard = None;
 
                 info!(
-                    "Been too long (>{}s) since last message heard to/from target GS {}",
+                    "Been too long (>{}s) since last message heard to/from any target GS",
                     self.last_heard_timeout,
-                    self.target.as_ref().unwrap()
                 );
 
                 self.determine_next_band();
                 return true;
             }
         } else if spdu_contains_target {
-            info!("Switching bands to bands heard from SPDU containing target");
             self.determine_next_band();
-            return true;
+            if self.next_band.is_some() {
+                info!("Switching bands to bands heard from SPDU containing targets");
+                return true;
+            }
         }
 
         false
@@ -269,4 +306,119 @@ impl ChooserPlugin for TrackerChooserPlugin {
 
         true
     }
+
+    fn timeout(&self, band: u32, timeout: Duration) -> Duration {
+        self.session.timeout(band, timeout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn bands() -> FrequencyBandMap {
+        HashMap::from([
+            (8, vec![8912, 8927]),
+            (13, vec![13276, 13312]),
+            (21, vec![21934]),
+        ])
+    }
+
+    fn tracker(targets: &[(&str, &[u32])]) -> TrackerChooserPlugin {
+        let mut tracker = TrackerChooserPlugin::new();
+        tracker.last_heard_timeout = 600;
+        for (spec, bands) in targets {
+            let mut target = Target::parse(spec).unwrap();
+            target.bands = bands.to_vec();
+            target.bands_last_updated = Some(Instant::now());
+            tracker.targets.push(target);
+        }
+        tracker
+    }
+
+    fn squitter(gs_id: u8, name: &str, freqs: &[f64]) -> Value {
+        let freqs: Vec<Value> = freqs.iter().map(|f| json!({"id": 0, "freq": f})).collect();
+        json!({"hfdl": {"freq": 8927000, "spdu": {
+            "err": false,
+            "src": {"type": "Ground station", "id": 7, "name": "Agana, Guam"},
+            "gs_status": [{
+                "gs": {"type": "Ground station", "id": gs_id, "name": name},
+                "utc_sync": true,
+                "freqs": freqs
+            }]
+        }}})
+    }
+
+    #[test]
+    fn parse_targets() {
+        let target = Target::parse("Agana").unwrap();
+        assert!(matches!(target.matcher, TargetMatch::Name(ref name) if name == "Agana"));
+        assert_eq!(target.priority, 1);
+
+        let target = Target::parse("17:2").unwrap();
+        assert!(matches!(target.matcher, TargetMatch::Id(17)));
+        assert_eq!(target.priority, 2);
+        assert_eq!(target.to_string(), "#17 (priority=2)");
+
+        assert!(Target::parse("").is_err());
+        assert!(Target::parse(":3").is_err());
+        assert!(Target::parse("Agana:0").is_err());
+        assert!(Target::parse("Agana:high").is_err());
+        assert!(Target::parse("Agana:").is_err());
+    }
+
+    #[test]
+    fn targets_match_by_name_prefix_or_ground_station_id() {
+        let gs: Entity = serde_json::from_value(
+            json!({"type": "Ground station", "id": 17, "name": "Canarias, Spain"}),
+        )
+        .unwrap();
+        let aircraft: Entity =
+            serde_json::from_value(json!({"type": "Aircraft", "id": 17})).unwrap();
+
+        assert!(Target::parse("Canarias").unwrap().matches(&gs));
+        assert!(!Target::parse("Spain").unwrap().matches(&gs));
+        assert!(Target::parse("17").unwrap().matches(&gs));
+        assert!(!Target::parse("17").unwrap().matches(&aircraft));
+    }
+
+    #[test]
+    fn highest_summed_priority_wins() {
+        let mut tracker = tracker(&[("Agana:3", &[21]), ("Shannon", &[8, 13]), ("17:3", &[13])]);
+
+        tracker.determine_next_band();
+        assert_eq!(tracker.next_band, Some(13));
+
+        // The band being listened to is never picked again
+        tracker.current_band = Some(13);
+        tracker.determine_next_band();
+        assert_eq!(tracker.next_band, Some(21));
+    }
+
+    #[test]
+    fn stale_target_bands_are_ignored() {
+        let mut tracker = tracker(&[("Agana:5", &[21]), ("Shannon", &[8])]);
+        tracker.targets[0].bands_last_updated =
+            Instant::now().checked_sub(Duration::from_secs(1300));
+
+        tracker.determine_next_band();
+        assert_eq!(tracker.next_band, Some(8));
+        assert!(tracker.targets[0].bands.is_empty());
+        assert!(tracker.targets[0].bands_last_updated.is_none());
+    }
+
+    #[test]
+    fn squitter_listing_target_switches_to_its_band() {
+        let bands = bands();
+        let props = HashMap::from([("target", "Shannon;17:2")]);
+        let mut tracker = TrackerChooserPlugin::new();
+        tracker.choose(&bands, &props).unwrap();
+        tracker.current_band = Some(8);
+
+        assert!(!tracker.on_update(&squitter(3, "Reykjavik, Iceland", &[21934.0])));
+        assert!(tracker.on_update(&squitter(17, "Canarias, Spain", &[13276.0, 8927.0])));
+        assert_eq!(tracker.targets[1].bands, vec![8, 13]);
+        assert_eq!(tracker.choose(&bands, &props), Ok(13));
+    }
 }
@@ -0,0 +1,375 @@
+use crossbeam::channel::{bounded, Receiver, Sender};
+use log::*;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use socket2::{Domain, Protocol, Socket, Type};
+use std::collections::{BTreeMap, HashMap};
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4, UdpSocket};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::config::FrequencyBandMap;
+use crate::dedupe::Digest;
+
+const ANNOUNCE_INTERVAL: Duration = Duration::from_secs(10);
+const PEER_EXPIRY: Duration = Duration::from_secs(35);
+/// Pause after a failed receive so a persistent socket error does not spin
+const RECV_RETRY_DELAY: Duration = Duration::from_secs(1);
+/// Digests from peers waiting to be merged before newer ones are dropped
+const DIGEST_BACKLOG: usize = 1024;
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Announcement {
+    instance: u64,
+    station: String,
+    bands: Vec<u32>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Message {
+    Announcement(Announcement),
+    /// A frame heard by one of the sender's receivers
+    Digest {
+        instance: u64,
+        digest: Digest,
+    },
+}
+
+struct Peer {
+    station: String,
+    bands: Vec<u32>,
+    last_seen: Instant,
+}
+
+/// Shared view of which bands every viper instance on the LAN is watching
+pub struct ClusterView {
+    instance: u64,
+    station: String,
+    bands: Vec<u32>,
+    peers: HashMap<u64, Peer>,
+}
+
+impl ClusterView {
+    fn live_peers(&self) -> impl Iterator<Item = &Peer> {
+        self.peers
+            .values()
+            .filter(|peer| peer.last_seen.elapsed() < PEER_EXPIRY)
+    }
+
+    /// Bands currently watched by peers, excluding this instance
+    pub fn peer_bands(&self) -> Vec<u32> {
+        let mut bands: Vec<u32> = self
+            .live_peers()
+            .flat_map(|peer| peer.bands.iter().copied())
+            .collect();
+        bands.sort_unstable();
+        bands.dedup();
+        bands
+    }
+
+    /// Returns the bands that no peer is currently watching
+    pub fn filter(&self, bands: &FrequencyBandMap) -> FrequencyBandMap {
+        let watched = self.peer_bands();
+        bands
+            .iter()
+            .filter(|(band, _)| !watched.contains(band))
+            .map(|(band, freqs)| (*band, freqs.clone()))
+            .collect()
+    }
+
+    pub fn snapshot(&self) -> Value {
+        let mut watched: BTreeMap<u32, Vec<&str>> = BTreeMap::new();
+        for band in self.bands.iter() {
+            watched.entry(*band).or_default().push(&self.station);
+        }
+        for peer in self.live_peers() {
+            for band in peer.bands.iter() {
+                watched.entry(*band).or_default().push(&peer.station);
+            }
+        }
+
+        json!({
+            "station": self.station,
+            "peers": self.live_peers().map(|p| &p.station).collect::<Vec<&String>>(),
+            "bands": watched,
+        })
+    }
+}
+
+/// Announces this instance's bands over UDP multicast and tracks the bands
+/// announced by peers. Digests of received frames are exchanged over the same
+/// group for cross-host deduplication.
+pub struct Cluster {
+    pub view: Arc<Mutex<ClusterView>>,
+    /// Digests of frames heard by peers
+    pub digests: Receiver<Digest>,
+
+    group: SocketAddr,
+    socket: Arc<UdpSocket>,
+}
+
+impl Cluster {
+    pub fn start(group: &str, station: &str) -> Result<Cluster, String> {
+        Cluster::join(group, station, Ipv4Addr::UNSPECIFIED)
+    }
+
+    /// Joins the multicast `group` on the network `interface` (unspecified for
+    /// the system default)
+    fn join(group: &str, station: &str, interface: Ipv4Addr) -> Result<Cluster, String> {
+        let group: SocketAddrV4 = group
+            .parse()
+            .map_err(|e| format!("Invalid cluster multicast address '{}': {}", group, e))?;
+        if !group.ip().is_multicast() {
+            return Err(format!("Cluster address is not multicast: {}", group));
+        }
+
+        let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))
+            .map_err(|e| format!("Unable to create cluster socket: {}", e))?;
+        socket
+            .set_reuse_address(true)
+            .and_then(|_| {
+                socket.bind(&SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, group.port()).into())
+            })
+            .and_then(|_| socket.join_multicast_v4(group.ip(), &interface))
+            .and_then(|_| socket.set_multicast_if_v4(&interface))
+            .and_then(|_| socket.set_multicast_loop_v4(true))
+            .and_then(|_| socket.set_multicast_ttl_v4(1))
+            .map_err(|e| format!("Unable to join cluster group {}: {}", group, e))?;
+        let socket: UdpSocket = socket.into();
+
+        let view = Arc::new(Mutex::new(ClusterView {
+            instance: rand::thread_rng().gen(),
+            station: station.to_string(),
+            bands: vec![],
+            peers: HashMap::new(),
+        }));
+
+        let listener = socket
+            .try_clone()
+            .map_err(|e| format!("Unable to clone cluster socket: {}", e))?;
+        let listener_view = Arc::clone(&view);
+        let (digest_send, digests) = bounded(DIGEST_BACKLOG);
+        thread::spawn(move || Cluster::listen(listener, listener_view, digest_send));
+
+        let socket = Arc::new(socket);
+        let group = SocketAddr::V4(group);
+
+        let announcer_view = Arc::clone(&view);
+        let announcer_socket = Arc::clone(&socket);
+        thread::spawn(move || loop {
+            thread::sleep(ANNOUNCE_INTERVAL);
+            Cluster::send(&announcer_socket, group, &announcer_view);
+        });
+
+        info!("Joined viper cluster on {}", group);
+        Ok(Cluster {
+            view,
+            digests,
+            group,
+            socket,
+        })
+    }
+
+    fn listen(socket: UdpSocket, view: Arc<Mutex<ClusterView>>, digests: Sender<Digest>) {
+        let mut buf = [0u8; 4096];
+
+        loop {
+            let size = match socket.recv_from(&mut buf) {
+                Ok((size, _)) => size,
+                Err(e) => {
+                    error!("Cluster listener failed to receive: {}", e);
+                    thread::sleep(RECV_RETRY_DELAY);
+                    continue;
+                }
+            };
+
+            let announcement = match serde_json::from_slice(&buf[..size]) {
+                Ok(Message::Announcement(announcement)) => announcement,
+                Ok(Message::Digest { instance, digest }) => {
+                    // Nobody drains the channel unless deduplication is on
+                    if instance != view.lock().unwrap().instance {
+                        let _ = digests.try_send(digest);
+                    }
+                    continue;
+                }
+                Err(e) => {
+                    warn!("Ignoring malformed cluster message: {}", e);
+                    continue;
+                }
+            };
+
+            let mut view = view.lock().unwrap();
+            if announcement.instance == view.instance {
+                continue;
+            }
+
+            let changed = match view.peers.get(&announcement.instance) {
+                Some(peer) => peer.bands != announcement.bands,
+                None => true,
+            };
+            if changed {
+                info!(
+                    "Cluster peer {} is watching bands {:?}",
+                    announcement.station, announcement.bands
+                );
+            }
+
+            view.peers.insert(
+                announcement.instance,
+                Peer {
+                    station: announcement.station,
+                    bands: announcement.bands,
+                    last_seen: Instant::now(),
+                },
+            );
+        }
+    }
+
+    pub fn set_bands(&self, bands: &[u32]) {
+        self.view.lock().unwrap().bands = bands.to_vec();
+        Cluster::send(&self.socket, self.group, &self.view);
+    }
+
+    /// Shares the digest of a frame heard by one of this instance's receivers
+    pub fn publish(&self, digest: &Digest) {
+        let message = Message::Digest {
+            instance: self.view.lock().unwrap().instance,
+            digest: digest.clone(),
+        };
+        Cluster::transmit(&self.socket, self.group, &message);
+    }
+
+    fn send(socket: &UdpSocket, group: SocketAddr, view: &Mutex<ClusterView>) {
+        let message = {
+            let view = view.lock().unwrap();
+            Message::Announcement(Announcement {
+                instance: view.instance,
+                station: view.station.clone(),
+                bands: view.bands.clone(),
+            })
+        };
+        Cluster::transmit(socket, group, &message);
+    }
+
+    fn transmit(socket: &UdpSocket, group: SocketAddr, message: &Message) {
+        match serde_json::to_vec(message) {
+            Ok(payload) => {
+                if let Err(e) = socket.send_to(&payload, group) {
+                    warn!("Failed to send cluster message: {}", e);
+                }
+            }
+            Err(e) => error!("Failed to encode cluster message: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn peer(station: &str, bands: &[u32], age: Duration) -> Peer {
+        Peer {
+            station: station.to_string(),
+            bands: bands.to_vec(),
+            last_seen: Instant::now().checked_sub(age).unwrap(),
+        }
+    }
+
+    fn bands() -> FrequencyBandMap {
+        FrequencyBandMap::from([
+            (8, vec![8927]),
+            (13, vec![13276]),
+            (17, vec![17919]),
+            (21, vec![21934]),
+        ])
+    }
+
+    #[test]
+    fn filter_skips_bands_of_live_peers_only() {
+        let view = ClusterView {
+            instance: 1,
+            station: "rooftop".to_string(),
+            bands: vec![8],
+            peers: HashMap::from([
+                (2, peer("garage", &[13, 21], Duration::ZERO)),
+                (3, peer("cabin", &[21], Duration::from_secs(5))),
+                (4, peer("gone", &[17], PEER_EXPIRY + Duration::from_secs(1))),
+            ]),
+        };
+
+        assert_eq!(view.peer_bands(), vec![13, 21]);
+
+        // The instance's own bands stay available to it
+        let mut unwatched: Vec<u32> = view.filter(&bands()).into_keys().collect();
+        unwatched.sort_unstable();
+        assert_eq!(unwatched, vec![8, 17]);
+
+        let snapshot = view.snapshot();
+        let mut watching: Vec<&str> = snapshot["bands"]["21"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter_map(Value::as_str)
+            .collect();
+        watching.sort_unstable();
+        assert_eq!(watching, vec!["cabin", "garage"]);
+        assert_eq!(snapshot["bands"]["8"], json!(["rooftop"]));
+        assert!(snapshot["bands"].get("17").is_none());
+    }
+
+    #[test]
+    fn peers_learn_bands_over_loopback() {
+        let group = format!(
+            "239.255.72.71:{}",
+            rand::thread_rng().gen_range(20000..60000)
+        );
+        let rooftop = Cluster::join(&group, "rooftop", Ipv4Addr::LOCALHOST).unwrap();
+        let garage = Cluster::join(&group, "garage", Ipv4Addr::LOCALHOST).unwrap();
+
+        rooftop.set_bands(&[13, 21]);
+        garage.set_bands(&[8]);
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while Instant::now() < deadline
+            && (garage.view.lock().unwrap().peer_bands() != vec![13, 21]
+                || rooftop.view.lock().unwrap().peer_bands() != vec![8])
+        {
+            thread::sleep(Duration::from_millis(20));
+        }
+
+        let view = garage.view.lock().unwrap();
+        assert_eq!(view.peer_bands(), vec![13, 21]);
+        assert_eq!(view.filter(&bands()).len(), 2);
+        assert_eq!(rooftop.view.lock().unwrap().peer_bands(), vec![8]);
+    }
+
+    #[test]
+    fn peers_receive_digests_over_loopback() {
+        let group = format!(
+            "239.255.72.71:{}",
+            rand::thread_rng().gen_range(20000..60000)
+        );
+        let rooftop = Cluster::join(&group, "rooftop", Ipv4Addr::LOCALHOST).unwrap();
+        let garage = Cluster::join(&group, "garage", Ipv4Addr::LOCALHOST).unwrap();
+
+        let digest = Digest {
+            key: 0xdeadbeef,
+            receiver: "rooftop".to_string(),
+            sig_level: Some(-21.5),
+        };
+        rooftop.publish(&digest);
+
+        assert_eq!(
+            garage.digests.recv_timeout(Duration::from_secs(5)),
+            Ok(digest)
+        );
+        // Instances ignore their own digests
+        assert!(rooftop
+            .digests
+            .recv_timeout(Duration::from_millis(200))
+            .is_err());
+    }
+}
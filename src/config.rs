@@ -1,17 +1,26 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use std::{env, fmt, fs};
 
+use crate::alerts::{AlertSpec, Watch};
+use crate::geo::Location;
+use crate::output::{SinkSpec, SinkSpecs, STDOUT_SINK};
+use crate::rules::{Action, Rule};
+
 pub type GroundStationMap = HashMap<String, GroundStation>;
 pub type FrequencyBandMap = HashMap<u32, Vec<u32>>;
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct GroundStation {
-    id: u32,
-    name: String,
-    lat: f64,
-    lon: f64,
+    pub id: u32,
+    pub name: String,
+    pub lat: f64,
+    pub lon: f64,
+
+    #[serde(default)]
+    pub frequencies: Vec<u32>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -21,18 +30,180 @@ pub struct HFDLInfo {
     pub raw: String,
 }
 
+pub const DEFAULT_MAX_SAMPLE_RATE: u32 = 384000;
+
+/// Widest spread (kHz) from a band's lowest frequency, matching the planner in
+/// `tools/convert_table.py`
+pub const BANDWIDTH_LIMIT: u32 = 384;
+/// Custom bands are keyed from here up so their keys never collide with system
+/// table bands, which are keyed by MHz or kHz
+pub const CUSTOM_BAND_KEYS: u32 = 1_000_000;
+
+pub const IQ_SAMPLE_FORMATS: [&str; 3] = ["cu8", "cs16", "cf32"];
+
+#[derive(Debug, Clone)]
+pub enum Source {
+    Soapy {
+        driver: String,
+    },
+    /// Recorded IQ samples centered on `centerfreq` (kHz), sampled at the
+    /// SDR's `max_sample_rate`
+    IqFile {
+        path: PathBuf,
+        format: String,
+        centerfreq: u32,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct Sdr {
+    pub source: Source,
+    pub max_sample_rate: u32,
+}
+
+impl Sdr {
+    /// Parses an SDR specification of the form `DRIVER[@MAX_SAMPLE_RATE]`
+    fn parse(spec: &str) -> Result<Sdr, String> {
+        let (driver, max_sample_rate) = match spec.rfind('@') {
+            Some(delim) => {
+                let rate = &spec[(delim + 1)..];
+                let rate: u32 = rate
+                    .parse()
+                    .map_err(|e| format!("SDR '{}' has an invalid sample rate: {}", spec, e))?;
+                (&spec[..delim], rate)
+            }
+            None => (spec, DEFAULT_MAX_SAMPLE_RATE),
+        };
+
+        if driver.is_empty() {
+            return Err(format!("SDR '{}' is missing a driver string", spec));
+        }
+
+        Ok(Sdr {
+            source: Source::Soapy {
+                driver: driver.to_string(),
+            },
+            max_sample_rate,
+        })
+    }
+
+    fn from_iq_args(args: &crate::args::Args, path: &Path) -> Result<Sdr, String> {
+        if !path.is_file() {
+            return Err(format!(
+                "IQ file does not exist or is not a file: {:?}",
+                path
+            ));
+        }
+
+        let format = args.iq_format.to_lowercase();
+        if !IQ_SAMPLE_FORMATS.contains(&format.as_str()) {
+            return Err(format!(
+                "Invalid IQ sample format '{}', expected one of {:?}",
+                args.iq_format, IQ_SAMPLE_FORMATS
+            ));
+        }
+
+        let max_sample_rate = args
+            .iq_sample_rate
+            .ok_or("--iq-sample-rate is required with --iq-file")?;
+        let centerfreq = args
+            .iq_centerfreq
+            .ok_or("--iq-centerfreq is required with --iq-file")?;
+
+        Ok(Sdr {
+            source: Source::IqFile {
+                path: path.to_path_buf(),
+                format,
+                centerfreq,
+            },
+            max_sample_rate,
+        })
+    }
+}
+
+impl fmt::Display for Sdr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.source {
+            Source::Soapy { driver } => write!(f, "{}@{}", driver, self.max_sample_rate),
+            Source::IqFile {
+                path,
+                format,
+                centerfreq,
+            } => write!(
+                f,
+                "iq:{}[{}]@{}/{}kHz",
+                path.display(),
+                format,
+                self.max_sample_rate,
+                centerfreq
+            ),
+        }
+    }
+}
+
+/// SoapySDR tuning options passed through to dumphfdl
+#[derive(Debug, Clone, Default)]
+pub struct Tuning {
+    pub gain: Option<f64>,
+    pub gain_elements: Option<String>,
+    pub antenna: Option<String>,
+    pub freq_correction: Option<f64>,
+    pub device_settings: Option<String>,
+    /// Offset in kHz applied to the center of the band when tuning
+    pub centerfreq_offset: Option<i32>,
+}
+
+/// Tuning options overridden for a single band
+#[derive(Debug, Clone, Default)]
+pub struct BandTuning {
+    pub gain: Option<f64>,
+    pub antenna: Option<String>,
+}
+
 #[derive(Debug)]
 pub struct Config {
     pub bin: PathBuf,
-    pub driver: String,
+    pub sdrs: Vec<Sdr>,
     pub output: Option<String>,
     pub timeout: u32,
+    pub band_timeouts: HashMap<u32, u32>,
+    pub tuning: Tuning,
+    pub band_tuning: HashMap<u32, BandTuning>,
+    pub auto_freq_correction: bool,
+    pub auto_gain: bool,
+    pub gain_file: Option<PathBuf>,
+    pub squitter_expiry: u32,
+    pub aircraft_expiry: u32,
+    pub aircraft_db: Option<PathBuf>,
+    pub airline_db: Option<PathBuf>,
+    pub aircraft_json: Option<PathBuf>,
+    pub aircraft_json_interval: u32,
+    pub sbs_addr: Option<String>,
+    pub track_log: Option<PathBuf>,
+    pub event_log: Option<PathBuf>,
+    pub sinks: SinkSpecs,
+    pub rules: Vec<Rule>,
+    pub watches: Vec<Watch>,
+    pub alerts: Vec<AlertSpec>,
+    pub alert_interval: u32,
+    pub quarantine_cooldown: Option<u32>,
+    pub quarantine_error_rate: f64,
+    pub quarantine_noise_margin: f64,
+    pub status_addr: Option<String>,
+    pub summary_interval: u32,
+    pub station: String,
+    pub enrich_gs: bool,
+    pub receiver_location: Option<Location>,
+    pub dedupe_window: Option<u64>,
+    pub cluster: Option<String>,
+    /// Custom band names and the keys they were added to `info.bands` under
+    pub band_names: HashMap<String, u32>,
 
     pub info: HFDLInfo,
 }
 
 impl Config {
-    fn parse_systable(path: &PathBuf) -> Result<HFDLInfo, String> {
+    pub fn parse_systable(path: &PathBuf) -> Result<HFDLInfo, String> {
         let contents = match fs::read_to_string(path) {
             Ok(contents) => contents,
             Err(e) => return Err(format!("Unable to read dumphfdl system table: {}", e)),
@@ -42,6 +213,241 @@ impl Config {
             .map_err(|e| format!("Unable to deserialize dumphfdl system table: {}", e))
     }
 
+    /// Groups frequencies into bands the same way `tools/convert_table.py`
+    /// does, keying each band by the MHz of its lowest frequency
+    fn plan_bands(freqs: &[u32]) -> FrequencyBandMap {
+        let mut freqs = freqs.to_vec();
+        freqs.sort_unstable();
+        freqs.dedup();
+
+        let mut bands: FrequencyBandMap = HashMap::new();
+        let mut current: Option<u32> = None;
+
+        for freq in freqs {
+            match current {
+                Some(band) if freq - bands[&band][0] <= BANDWIDTH_LIMIT => {
+                    bands.get_mut(&band).unwrap().push(freq);
+                }
+                _ => {
+                    let mut band = freq / 1000;
+                    if bands.contains_key(&band) {
+                        band = freq;
+                    }
+                    bands.insert(band, vec![freq]);
+                    current = Some(band);
+                }
+            }
+        }
+
+        bands
+    }
+
+    /// Removes excluded bands and frequencies from the system table, then
+    /// re-plans the remaining frequencies so exclusions no longer widen bands
+    fn apply_exclusions(info: &mut HFDLInfo, bands: &[u32], freqs: &[u32]) -> Result<(), String> {
+        if bands.is_empty() && freqs.is_empty() {
+            return Ok(());
+        }
+
+        let mut excluded: Vec<u32> = freqs.to_vec();
+        for band in bands.iter() {
+            match info.bands.get(band) {
+                Some(band_freqs) => excluded.extend(band_freqs.iter()),
+                None => {
+                    return Err(format!(
+                        "Excluded band is not in the system table: {}",
+                        band
+                    ))
+                }
+            }
+        }
+        for freq in freqs.iter() {
+            if !info.bands.values().any(|f| f.contains(freq)) {
+                return Err(format!(
+                    "Excluded frequency is not in the system table: {}",
+                    freq
+                ));
+            }
+        }
+
+        let remaining: Vec<u32> = info
+            .bands
+            .values()
+            .flatten()
+            .filter(|f| !excluded.contains(f))
+            .copied()
+            .collect();
+        if remaining.is_empty() {
+            return Err("Exclusions leave no frequencies to listen to".to_string());
+        }
+
+        info.bands = Config::plan_bands(&remaining);
+        Ok(())
+    }
+
+    /// Parses repeatable `NAME=KHZ,KHZ,...` custom bands and adds them to the
+    /// system table's bands, keyed from `CUSTOM_BAND_KEYS` in definition order
+    fn parse_custom_bands(
+        specs: &[String],
+        info: &mut HFDLInfo,
+        sdrs: &[Sdr],
+    ) -> Result<HashMap<String, u32>, String> {
+        let mut names: HashMap<String, u32> = HashMap::new();
+
+        for spec in specs.iter() {
+            let (name, freqs) = match spec.find('=') {
+                Some(delim) => (&spec[..delim], &spec[(delim + 1)..]),
+                None => return Err(format!("Custom band is not NAME=KHZ,KHZ,...: {}", spec)),
+            };
+
+            if name.is_empty() || name.parse::<u32>().is_ok() {
+                return Err(format!("Custom band name must be non-numeric: '{}'", name));
+            }
+            if names.contains_key(name) {
+                return Err(format!("Custom band defined more than once: {}", name));
+            }
+
+            let mut freqs = freqs
+                .split(',')
+                .map(|freq| {
+                    freq.parse::<u32>().map_err(|e| {
+                        format!(
+                            "Custom band {} has an invalid frequency '{}': {}",
+                            name, freq, e
+                        )
+                    })
+                })
+                .collect::<Result<Vec<u32>, String>>()?;
+            freqs.sort_unstable();
+            freqs.dedup();
+
+            for freq in freqs.iter() {
+                if !info.bands.values().any(|f| f.contains(freq)) {
+                    return Err(format!(
+                        "Custom band {} frequency is not in the system table: {}",
+                        name, freq
+                    ));
+                }
+            }
+            if !sdrs
+                .iter()
+                .any(|sdr| crate::receiver::sdr_sample_rate(sdr, &freqs).is_some())
+            {
+                return Err(format!(
+                    "Custom band {} is too wide for any SDR: {:?}",
+                    name, freqs
+                ));
+            }
+
+            let band = CUSTOM_BAND_KEYS + names.len() as u32;
+            info.bands.insert(band, freqs);
+            names.insert(name.to_string(), band);
+        }
+
+        Ok(names)
+    }
+
+    /// Parses `NAME=SINK` sinks and the rules routing frames to them
+    fn parse_routing(sinks: &[String], rules: &[String]) -> Result<(SinkSpecs, Vec<Rule>), String> {
+        let sinks = sinks
+            .iter()
+            .map(|entry| match entry.split_once('=') {
+                Some((name, spec)) if !name.is_empty() => {
+                    Ok((name.to_string(), SinkSpec::parse(spec)?))
+                }
+                _ => Err(format!("Sink is not NAME=SINK: {}", entry)),
+            })
+            .collect::<Result<SinkSpecs, String>>()?;
+
+        let rules = rules
+            .iter()
+            .map(|spec| Rule::parse(spec))
+            .collect::<Result<Vec<Rule>, String>>()?;
+
+        for rule in rules.iter() {
+            if let Action::Route(name) = &rule.action {
+                if name != STDOUT_SINK && !sinks.iter().any(|(sink, _)| sink == name) {
+                    return Err(format!("Rule routes to an undefined sink: {}", name));
+                }
+            }
+        }
+
+        Ok((sinks, rules))
+    }
+
+    /// Looks up a band by key or custom band name. Custom bands can only be
+    /// referred to by name.
+    pub fn parse_band(band: &str, names: &HashMap<String, u32>) -> Option<u32> {
+        match names.get(band) {
+            Some(key) => Some(*key),
+            None => band.parse().ok().filter(|key| *key < CUSTOM_BAND_KEYS),
+        }
+    }
+
+    /// Parses repeatable `BAND=VALUE` overrides, described as `what` in errors
+    fn parse_band_overrides<T>(
+        what: &str,
+        overrides: &[String],
+        bands: &FrequencyBandMap,
+        names: &HashMap<String, u32>,
+    ) -> Result<HashMap<u32, T>, String>
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        let mut parsed: HashMap<u32, T> = HashMap::new();
+
+        for entry in overrides.iter() {
+            let (band, value) = match entry.find('=') {
+                Some(delim) => (&entry[..delim], &entry[(delim + 1)..]),
+                None => return Err(format!("Band {} is not BAND=VALUE: {}", what, entry)),
+            };
+
+            let band: u32 = match Config::parse_band(band, names) {
+                Some(band) if bands.contains_key(&band) => band,
+                _ => return Err(format!("Band {} refers to an unknown band: {}", what, band)),
+            };
+
+            let value: T = value
+                .parse()
+                .map_err(|e| format!("Band {} has an invalid value '{}': {}", what, value, e))?;
+
+            parsed.insert(band, value);
+        }
+
+        Ok(parsed)
+    }
+
+    pub fn tuning_for(&self, band: u32) -> Tuning {
+        let mut tuning = self.tuning.clone();
+
+        if let Some(overrides) = self.band_tuning.get(&band) {
+            if overrides.gain.is_some() {
+                tuning.gain = overrides.gain;
+            }
+            if overrides.antenna.is_some() {
+                tuning.antenna = overrides.antenna.clone();
+            }
+        }
+
+        tuning
+    }
+
+    /// Offline sessions replay recordings and end once they are exhausted
+    pub fn is_offline(&self) -> bool {
+        self.sdrs
+            .iter()
+            .all(|sdr| matches!(sdr.source, Source::IqFile { .. }))
+    }
+
+    pub fn receiver_name(&self, idx: usize) -> String {
+        format!("{}/sdr{}", self.station, idx)
+    }
+
+    pub fn timeout_for(&self, band: u32) -> u32 {
+        *self.band_timeouts.get(&band).unwrap_or(&self.timeout)
+    }
+
     pub fn from_args(args: &crate::args::Args) -> Result<Config, String> {
         if !args.bin.exists() || !args.bin.is_file() {
             return Err(format!(
@@ -59,7 +465,7 @@ impl Config {
         let soapy_driver = env::var("VIPER_SOAPY_DRIVER").map_or_else(
             |_| args.driver.clone(),
             |val| {
-                if val.len() > 0 {
+                if !val.is_empty() {
                     val
                 } else {
                     args.driver.clone()
@@ -67,13 +473,117 @@ impl Config {
             },
         );
 
-        let info = Config::parse_systable(&args.sys_table)?;
+        let sdrs = if let Some(path) = &args.iq_file {
+            if !args.sdr.is_empty() {
+                return Err("--iq-file cannot be combined with --sdr".to_string());
+            }
+            vec![Sdr::from_iq_args(args, path)?]
+        } else if args.sdr.is_empty() {
+            vec![Sdr {
+                source: Source::Soapy {
+                    driver: soapy_driver,
+                },
+                max_sample_rate: DEFAULT_MAX_SAMPLE_RATE,
+            }]
+        } else {
+            args.sdr
+                .iter()
+                .map(|spec| Sdr::parse(spec))
+                .collect::<Result<Vec<Sdr>, String>>()?
+        };
+
+        let mut info = Config::parse_systable(&args.sys_table)?;
+        Config::apply_exclusions(&mut info, &args.exclude_band, &args.exclude_freq)?;
+        let band_names = Config::parse_custom_bands(&args.custom_band, &mut info, &sdrs)?;
+        let band_timeouts =
+            Config::parse_band_overrides("timeout", &args.band_timeout, &info.bands, &band_names)?;
+        let band_gains =
+            Config::parse_band_overrides("gain", &args.band_gain, &info.bands, &band_names)?;
+        let mut band_antennas: HashMap<u32, String> =
+            Config::parse_band_overrides("antenna", &args.band_antenna, &info.bands, &band_names)?;
+
+        let mut band_tuning: HashMap<u32, BandTuning> = HashMap::new();
+        for (band, gain) in band_gains.into_iter() {
+            band_tuning.entry(band).or_default().gain = Some(gain);
+        }
+        for (band, antenna) in band_antennas.drain() {
+            band_tuning.entry(band).or_default().antenna = Some(antenna);
+        }
+
+        if !(0.0..=1.0).contains(&args.quarantine_error_rate) {
+            return Err(format!(
+                "Quarantine error rate must be between 0 and 1: {}",
+                args.quarantine_error_rate
+            ));
+        }
+
+        let (sinks, rules) = Config::parse_routing(&args.sink, &args.rule)?;
+
+        let watches = args
+            .watch
+            .iter()
+            .map(|spec| Watch::parse(spec))
+            .collect::<Result<Vec<Watch>, String>>()?;
+        let mut alerts = args
+            .alert
+            .iter()
+            .map(|spec| AlertSpec::parse(spec))
+            .collect::<Result<Vec<AlertSpec>, String>>()?;
+        if alerts.is_empty() {
+            alerts.push(AlertSpec::Log);
+        }
+
+        let receiver_location = args
+            .receiver_location
+            .as_deref()
+            .map(Location::parse)
+            .transpose()?;
+
+        let tuning = Tuning {
+            gain: args.gain,
+            gain_elements: args.gain_elements.clone(),
+            antenna: args.antenna.clone(),
+            freq_correction: args.freq_correction,
+            device_settings: args.device_settings.clone(),
+            centerfreq_offset: args.centerfreq_offset,
+        };
 
         Ok(Config {
             bin: args.bin.clone(),
-            driver: soapy_driver,
+            sdrs,
             output: args.output.clone(),
             timeout: args.timeout,
+            band_timeouts,
+            tuning,
+            band_tuning,
+            auto_freq_correction: args.auto_freq_correction,
+            auto_gain: args.auto_gain,
+            gain_file: args.gain_file.clone(),
+            squitter_expiry: args.squitter_expiry,
+            aircraft_expiry: args.aircraft_expiry,
+            aircraft_db: args.aircraft_db.clone(),
+            airline_db: args.airline_db.clone(),
+            aircraft_json: args.aircraft_json.clone(),
+            aircraft_json_interval: args.aircraft_json_interval,
+            sbs_addr: args.sbs_addr.clone(),
+            track_log: args.track_log.clone(),
+            event_log: args.event_log.clone(),
+            sinks,
+            rules,
+            watches,
+            alerts,
+            alert_interval: args.alert_interval,
+            quarantine_cooldown: args.quarantine_cooldown,
+            quarantine_error_rate: args.quarantine_error_rate,
+            quarantine_noise_margin: args.quarantine_noise_margin,
+            status_addr: args.status_addr.clone(),
+            summary_interval: args.summary_interval,
+            station: args.station.clone(),
+            enrich_gs: args.enrich_gs || receiver_location.is_some(),
+            receiver_location,
+            dedupe_window: args.dedupe_window,
+            cluster: args.cluster.clone(),
+            band_names,
             info,
         })
     }
@@ -83,8 +593,164 @@ impl fmt::Display for Config {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
-            "Config {{ bin={:?}, driver={}, output={:?} timeout={}s }}",
-            self.bin, self.driver, self.output, self.timeout
+            "Config {{ bin={:?}, sdrs=[{}], output={:?} timeout={}s band_timeouts={:?} squitter_expiry={}s }}",
+            self.bin,
+            self.sdrs
+                .iter()
+                .map(|sdr| sdr.to_string())
+                .collect::<Vec<String>>()
+                .join(", "),
+            self.output,
+            self.timeout,
+            self.band_timeouts,
+            self.squitter_expiry
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::receiver;
+
+    fn systable() -> HFDLInfo {
+        let path = PathBuf::from(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/testing/systable.json"
+        ));
+        Config::parse_systable(&path).unwrap()
+    }
+
+    /// Bands with their frequencies sorted and deduplicated, as the system
+    /// table may list a frequency once per station sharing it
+    fn normalized(bands: &FrequencyBandMap) -> FrequencyBandMap {
+        bands
+            .iter()
+            .map(|(band, freqs)| {
+                let mut freqs = freqs.clone();
+                freqs.sort_unstable();
+                freqs.dedup();
+                (*band, freqs)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn plan_bands_reproduces_systable() {
+        let info = systable();
+        let freqs: Vec<u32> = info.bands.values().flatten().copied().collect();
+
+        assert_eq!(Config::plan_bands(&freqs), normalized(&info.bands));
+    }
+
+    #[test]
+    fn no_exclusions_keep_bands() {
+        let mut info = systable();
+        let bands = info.bands.clone();
+
+        Config::apply_exclusions(&mut info, &[], &[]).unwrap();
+        assert_eq!(info.bands, bands);
+    }
+
+    #[test]
+    fn excluding_edge_frequency_narrows_band() {
+        let mut info = systable();
+        assert_eq!(receiver::sample_rate(&info.bands[&5]), Some(384000));
+
+        Config::apply_exclusions(&mut info, &[], &[5720]).unwrap();
+        let band = &info.bands[&5];
+        assert!(!band.contains(&5720));
+        assert_eq!(band.iter().max(), Some(&5655));
+        assert_eq!(receiver::sample_rate(band), Some(256000));
+    }
+
+    #[test]
+    fn exclusions_must_be_in_systable() {
+        let mut info = systable();
+
+        assert!(Config::apply_exclusions(&mut info, &[7], &[]).is_err());
+        assert!(Config::apply_exclusions(&mut info, &[], &[12345]).is_err());
+
+        let all: Vec<u32> = info.bands.keys().copied().collect();
+        assert!(Config::apply_exclusions(&mut info, &all, &[]).is_err());
+    }
+
+    #[test]
+    fn band_timeouts() {
+        let info = systable();
+        let names = HashMap::from([("pacific".to_string(), 13)]);
+        let overrides = ["13=300".to_string(), "21=90".to_string()];
+
+        let timeouts: HashMap<u32, u32> =
+            Config::parse_band_overrides("timeout", &overrides, &info.bands, &names).unwrap();
+        assert_eq!(timeouts, HashMap::from([(13, 300), (21, 90)]));
+
+        let by_name = ["pacific=120".to_string()];
+        let timeouts: HashMap<u32, u32> =
+            Config::parse_band_overrides("timeout", &by_name, &info.bands, &names).unwrap();
+        assert_eq!(timeouts, HashMap::from([(13, 120)]));
+
+        for bad in ["7=300", "13", "13=soon"] {
+            assert!(Config::parse_band_overrides::<u32>(
+                "timeout",
+                &[bad.to_string()],
+                &info.bands,
+                &names
+            )
+            .is_err());
+        }
+    }
+
+    #[test]
+    fn custom_bands_have_their_own_keys() {
+        let mut info = systable();
+        let system_bands = info.bands.len();
+        let sdrs = [Sdr::parse("driver=airspyhf").unwrap()];
+        let specs = ["pacific=13276,13312".to_string(), "low=8927".to_string()];
+
+        let names = Config::parse_custom_bands(&specs, &mut info, &sdrs).unwrap();
+        assert_eq!(names["pacific"], CUSTOM_BAND_KEYS);
+        assert_eq!(names["low"], CUSTOM_BAND_KEYS + 1);
+        assert_eq!(info.bands.len(), system_bands + 2);
+        assert_eq!(info.bands[&CUSTOM_BAND_KEYS], vec![13276, 13312]);
+
+        // Numbers only ever refer to system table bands
+        assert_eq!(Config::parse_band("8927", &names), Some(8927));
+        assert_eq!(
+            Config::parse_band("low", &names),
+            Some(CUSTOM_BAND_KEYS + 1)
+        );
+        assert_eq!(
+            Config::parse_band(&CUSTOM_BAND_KEYS.to_string(), &names),
+            None
+        );
+
+        for bad in [
+            vec!["13=13276"],
+            vec!["pacific=13276", "pacific=13312"],
+            vec!["far=12345"],
+            vec!["wide=2941,21934"],
+        ] {
+            let bad: Vec<String> = bad.iter().map(|spec| spec.to_string()).collect();
+            assert!(Config::parse_custom_bands(&bad, &mut info, &sdrs).is_err());
+        }
+    }
+
+    #[test]
+    fn parse_sdr_specs() {
+        let sdr = Sdr::parse("driver=airspyhf").unwrap();
+        assert_eq!(sdr.max_sample_rate, DEFAULT_MAX_SAMPLE_RATE);
+        assert_eq!(sdr.to_string(), "driver=airspyhf@384000");
+
+        let sdr = Sdr::parse("driver=rtlsdr,serial=a@b@256000").unwrap();
+        assert_eq!(sdr.max_sample_rate, 256000);
+        assert!(
+            matches!(sdr.source, Source::Soapy { ref driver } if driver == "driver=rtlsdr,serial=a@b")
+        );
+
+        assert!(Sdr::parse("").is_err());
+        assert!(Sdr::parse("@256000").is_err());
+        assert!(Sdr::parse("driver=airspyhf@fast").is_err());
+        assert!(Sdr::parse("driver=airspyhf@").is_err());
+    }
+}
@@ -0,0 +1,322 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crate::frame::annotate;
+
+/// Fields that differ between receivers hearing the same transmission
+const RECEIVER_FIELDS: [&str; 6] = [
+    "t",
+    "sig_level",
+    "noise_level",
+    "freq_skew",
+    "station",
+    "app",
+];
+
+/// Announces that a receiver heard a frame, so viper instances sharing a
+/// cluster can merge their copies without exchanging whole frames
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Digest {
+    pub key: u64,
+    pub receiver: String,
+    pub sig_level: Option<f64>,
+}
+
+/// A receiver that heard a pending frame
+struct Copy {
+    receiver: String,
+    sig_level: Option<f64>,
+    /// Heard by a receiver of another viper instance
+    remote: bool,
+}
+
+struct Pending {
+    first_seen: Instant,
+    /// Strongest copy heard locally, if any
+    frame: Option<Value>,
+    copies: Vec<Copy>,
+}
+
+impl Copy {
+    /// Orders copies by signal level; ties go to the receiver whose name sorts
+    /// first so every instance picks the same copy
+    fn cmp_strength(&self, other: &Copy) -> Ordering {
+        let level = |c: &Copy| c.sig_level.unwrap_or(f64::NEG_INFINITY);
+        level(self)
+            .total_cmp(&level(other))
+            .then_with(|| other.receiver.cmp(&self.receiver))
+    }
+}
+
+impl Pending {
+    fn best(&self) -> Option<&Copy> {
+        self.copies.iter().max_by(|a, b| a.cmp_strength(b))
+    }
+
+    fn best_local(&self) -> Option<&Copy> {
+        self.copies
+            .iter()
+            .filter(|c| !c.remote)
+            .max_by(|a, b| a.cmp_strength(b))
+    }
+}
+
+/// Holds frames for a short window so copies of the same transmission heard
+/// by several receivers are merged into one frame tagged with every receiver
+/// that heard it. The copy with the strongest signal is kept.
+///
+/// Copies heard by other viper instances arrive as digests. Every instance
+/// applies the same rule, so only the one holding the strongest copy outputs
+/// the merged frame and the others drop theirs.
+pub struct Deduplicator {
+    window: Duration,
+    pending: HashMap<u64, Pending>,
+}
+
+/// 64-bit FNV-1a, stable across builds and hosts unlike `DefaultHasher`
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Identifies a transmission by the decoded content of its frame
+fn content_key(frame: &Value) -> u64 {
+    let mut hfdl = frame.get("hfdl").cloned().unwrap_or(Value::Null);
+    if let Some(fields) = hfdl.as_object_mut() {
+        for field in RECEIVER_FIELDS.iter() {
+            fields.remove(*field);
+        }
+    }
+
+    fnv1a(hfdl.to_string().as_bytes())
+}
+
+fn sig_level(frame: &Value) -> Option<f64> {
+    frame
+        .get("hfdl")
+        .and_then(|hfdl| hfdl.get("sig_level"))
+        .and_then(|level| level.as_f64())
+}
+
+impl Deduplicator {
+    pub fn new(window: Duration) -> Self {
+        Deduplicator {
+            window,
+            pending: HashMap::new(),
+        }
+    }
+
+    fn pending(&mut self, key: u64) -> &mut Pending {
+        self.pending.entry(key).or_insert_with(|| Pending {
+            first_seen: Instant::now(),
+            frame: None,
+            copies: vec![],
+        })
+    }
+
+    /// Holds a frame heard by a local receiver. Returns the digest to share
+    /// with other instances.
+    pub fn push(&mut self, receiver: &str, frame: Value) -> Digest {
+        let digest = Digest {
+            key: content_key(&frame),
+            receiver: receiver.to_string(),
+            sig_level: sig_level(&frame),
+        };
+
+        let pending = self.pending(digest.key);
+        if !pending.copies.iter().any(|c| c.receiver == receiver) {
+            pending.copies.push(Copy {
+                receiver: digest.receiver.clone(),
+                sig_level: digest.sig_level,
+                remote: false,
+            });
+        }
+        if pending
+            .best_local()
+            .is_some_and(|best| best.receiver == receiver)
+        {
+            pending.frame = Some(frame);
+        }
+
+        digest
+    }
+
+    /// Records a copy heard by another viper instance
+    pub fn merge(&mut self, digest: Digest) {
+        let pending = self.pending(digest.key);
+        if pending.copies.iter().any(|c| c.receiver == digest.receiver) {
+            return;
+        }
+
+        pending.copies.push(Copy {
+            receiver: digest.receiver,
+            sig_level: digest.sig_level,
+            remote: true,
+        });
+    }
+
+    /// Returns the frames whose window has elapsed, oldest first
+    pub fn poll(&mut self) -> Vec<Value> {
+        self.flush(false)
+    }
+
+    /// Returns every pending frame regardless of its window
+    pub fn drain(&mut self) -> Vec<Value> {
+        self.flush(true)
+    }
+
+    fn flush(&mut self, all: bool) -> Vec<Value> {
+        let due: Vec<u64> = self
+            .pending
+            .iter()
+            .filter(|(_, p)| all || p.first_seen.elapsed() >= self.window)
+            .map(|(key, _)| *key)
+            .collect();
+
+        let mut ready: Vec<Pending> = due
+            .into_iter()
+            .filter_map(|key| self.pending.remove(&key))
+            .collect();
+        ready.sort_by_key(|p| p.first_seen);
+
+        ready
+            .into_iter()
+            .filter_map(|p| {
+                // Frames another instance heard best are output by that instance
+                let best = p.best().filter(|best| !best.remote)?;
+                let (best_receiver, best_sig_level) = (best.receiver.clone(), best.sig_level);
+                let receivers: Vec<&str> = p.copies.iter().map(|c| c.receiver.as_str()).collect();
+                let receivers = json!(receivers);
+
+                let mut frame = p.frame?;
+                annotate(&mut frame, "receivers", receivers);
+                annotate(&mut frame, "best_receiver", json!(best_receiver));
+                annotate(&mut frame, "best_sig_level", json!(best_sig_level));
+                Some(frame)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    fn frame(t: u64, sig_level: f64) -> Value {
+        json!({"hfdl": {
+            "t": {"sec": t, "usec": 0},
+            "freq": 13276000,
+            "sig_level": sig_level,
+            "noise_level": -45.0,
+            "freq_skew": 1.2,
+            "station": "rooftop",
+            "lpdu": {"src": {"type": "Aircraft", "id": 42}, "type": {"id": 13, "name": "Logon request"}}
+        }})
+    }
+
+    fn remote(key: u64, receiver: &str, sig_level: f64) -> Digest {
+        Digest {
+            key,
+            receiver: receiver.to_string(),
+            sig_level: Some(sig_level),
+        }
+    }
+
+    #[test]
+    fn key_ignores_receiver_fields() {
+        assert_eq!(
+            content_key(&frame(100, -20.0)),
+            content_key(&frame(101, -35.0))
+        );
+
+        let mut other = frame(100, -20.0);
+        other["hfdl"]["freq"] = json!(13312000);
+        assert_ne!(content_key(&frame(100, -20.0)), content_key(&other));
+        assert_ne!(content_key(&frame(100, -20.0)), content_key(&json!({})));
+    }
+
+    #[test]
+    fn keeps_strongest_copy() {
+        let mut dedupe = Deduplicator::new(Duration::from_secs(60));
+        let digest = dedupe.push("rooftop/sdr0", frame(100, -30.0));
+        assert_eq!(digest.sig_level, Some(-30.0));
+        dedupe.push("rooftop/sdr1", frame(101, -20.0));
+        dedupe.push("rooftop/sdr2", frame(102, -25.0));
+
+        let frames = dedupe.drain();
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0]["hfdl"]["sig_level"], json!(-20.0));
+        assert_eq!(
+            frames[0]["viper"]["receivers"],
+            json!(["rooftop/sdr0", "rooftop/sdr1", "rooftop/sdr2"])
+        );
+        assert_eq!(frames[0]["viper"]["best_receiver"], json!("rooftop/sdr1"));
+        assert!(dedupe.drain().is_empty());
+    }
+
+    #[test]
+    fn frames_are_held_for_the_window() {
+        let mut dedupe = Deduplicator::new(Duration::from_millis(50));
+        dedupe.push("rooftop/sdr0", frame(100, -30.0));
+        assert!(dedupe.poll().is_empty());
+
+        thread::sleep(Duration::from_millis(60));
+        assert_eq!(dedupe.poll().len(), 1);
+
+        // A copy arriving after the window is output on its own
+        dedupe.push("rooftop/sdr1", frame(101, -20.0));
+        thread::sleep(Duration::from_millis(60));
+        let frames = dedupe.poll();
+        assert_eq!(frames[0]["viper"]["receivers"], json!(["rooftop/sdr1"]));
+    }
+
+    #[test]
+    fn stronger_remote_copy_drops_local_frame() {
+        let mut dedupe = Deduplicator::new(Duration::from_secs(60));
+        let digest = dedupe.push("rooftop/sdr0", frame(100, -30.0));
+        dedupe.merge(remote(digest.key, "garage/sdr0", -10.0));
+        assert!(dedupe.drain().is_empty());
+    }
+
+    #[test]
+    fn weaker_remote_copy_is_merged() {
+        let mut dedupe = Deduplicator::new(Duration::from_secs(60));
+        let digest = dedupe.push("rooftop/sdr0", frame(100, -30.0));
+        dedupe.merge(remote(digest.key, "garage/sdr0", -40.0));
+        dedupe.merge(remote(digest.key, "garage/sdr0", -40.0));
+
+        let frames = dedupe.drain();
+        assert_eq!(
+            frames[0]["viper"]["receivers"],
+            json!(["rooftop/sdr0", "garage/sdr0"])
+        );
+        assert_eq!(frames[0]["viper"]["best_receiver"], json!("rooftop/sdr0"));
+    }
+
+    #[test]
+    fn remote_only_copies_are_not_output() {
+        let mut dedupe = Deduplicator::new(Duration::from_secs(60));
+        dedupe.merge(remote(1, "garage/sdr0", -30.0));
+        assert!(dedupe.drain().is_empty());
+    }
+
+    #[test]
+    fn equal_copies_go_to_the_first_receiver_by_name() {
+        // Both hosts see the same copies and must agree on which outputs it
+        let mut rooftop = Deduplicator::new(Duration::from_secs(60));
+        let digest = rooftop.push("rooftop/sdr0", frame(100, -30.0));
+        rooftop.merge(remote(digest.key, "garage/sdr0", -30.0));
+
+        let mut garage = Deduplicator::new(Duration::from_secs(60));
+        garage.push("garage/sdr0", frame(100, -30.0));
+        garage.merge(digest);
+
+        assert!(rooftop.drain().is_empty());
+        assert_eq!(garage.drain().len(), 1);
+    }
+}
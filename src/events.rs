@@ -0,0 +1,298 @@
+use log::*;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+
+use crate::frame::{Entity, Lpdu, MessageFrame};
+use crate::util::unix_now;
+
+const PERFORMANCE_DATA: &str = "Performance data";
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    LogonRequest,
+    Logon,
+    LogonResume,
+    LogonDenied,
+    Logoff,
+}
+
+impl EventKind {
+    fn from_lpdu(lpdu: &Lpdu) -> Option<EventKind> {
+        let name = lpdu.kind.as_ref()?.name.as_str();
+
+        match name {
+            "Logon confirm" => Some(EventKind::Logon),
+            "Logon resume confirm" => Some(EventKind::LogonResume),
+            "Logon denied" => Some(EventKind::LogonDenied),
+            "Logoff request" => Some(EventKind::Logoff),
+            _ if name.starts_with("Logon request") => Some(EventKind::LogonRequest),
+            _ => None,
+        }
+    }
+}
+
+/// An aircraft's HFDL session changing state on a ground station
+#[derive(Serialize, Clone, Debug)]
+pub struct Event {
+    pub ts: u64,
+    pub event: EventKind,
+    pub gs: u32,
+    pub gs_name: Option<String>,
+    /// Frequency in kHz
+    pub freq: u32,
+    pub ac_id: Option<u8>,
+    pub icao: Option<String>,
+}
+
+/// Traffic statistics of a single ground station
+#[derive(Serialize, Clone, Debug)]
+pub struct GsLoad {
+    pub id: u32,
+    pub name: Option<String>,
+
+    /// Aircraft currently logged on
+    pub aircraft: usize,
+    pub logon_requests: u64,
+    pub logons: u64,
+    pub logon_denials: u64,
+    pub logoffs: u64,
+    pub frames: u64,
+    pub performance_reports: u64,
+
+    /// Aircraft ids logged on, their ICAO address and when each was last heard
+    #[serde(skip)]
+    logged_on: HashMap<u8, (Option<String>, u64)>,
+}
+
+/// Derives logon/logoff events from link-layer traffic and keeps per ground
+/// station load statistics
+pub struct LoadTable {
+    expiry: Duration,
+    stations: BTreeMap<u32, GsLoad>,
+}
+
+impl LoadTable {
+    pub fn new(expiry: Duration) -> Self {
+        LoadTable {
+            expiry,
+            stations: BTreeMap::new(),
+        }
+    }
+
+    pub fn update(&mut self, msg: &MessageFrame) -> Option<Event> {
+        let lpdu = msg.hfdl.lpdu.as_ref()?;
+        let (gs, aircraft): (&Entity, &Entity) = if lpdu.src.is_ground_station() {
+            (&lpdu.src, &lpdu.dst)
+        } else if lpdu.dst.is_ground_station() {
+            (&lpdu.dst, &lpdu.src)
+        } else {
+            return None;
+        };
+
+        let now = unix_now();
+        let expiry = self.expiry.as_secs();
+
+        let load = self.stations.entry(gs.id as u32).or_insert_with(|| GsLoad {
+            id: gs.id as u32,
+            name: None,
+            aircraft: 0,
+            logon_requests: 0,
+            logons: 0,
+            logon_denials: 0,
+            logoffs: 0,
+            frames: 0,
+            performance_reports: 0,
+            logged_on: HashMap::new(),
+        });
+        if gs.entity_name.is_some() {
+            load.name = gs.entity_name.clone();
+        }
+
+        load.frames += 1;
+        if let Some((_, seen)) = load.logged_on.get_mut(&aircraft.id) {
+            *seen = now;
+        }
+
+        let performance = lpdu
+            .hfnpdu
+            .as_ref()
+            .and_then(|hfnpdu| hfnpdu.kind.as_ref())
+            .is_some_and(|kind| kind.name == PERFORMANCE_DATA);
+        if performance {
+            load.performance_reports += 1;
+        }
+
+        // Aircraft that leave coverage never log off, so drop ones not heard
+        // from within the expiry
+        load.logged_on
+            .retain(|_, (_, seen)| now.saturating_sub(*seen) < expiry);
+
+        let kind = EventKind::from_lpdu(lpdu);
+        let ac_id = match kind {
+            Some(EventKind::Logon) => lpdu.assigned_ac_id,
+            _ => Some(aircraft.id),
+        };
+        let icao = aircraft
+            .ac_info
+            .as_ref()
+            .or(lpdu.ac_info.as_ref())
+            .map(|info| info.icao.clone())
+            .or_else(|| {
+                ac_id
+                    .and_then(|id| load.logged_on.get(&id))
+                    .and_then(|(icao, _)| icao.clone())
+            });
+
+        match kind {
+            Some(EventKind::LogonRequest) => load.logon_requests += 1,
+            Some(EventKind::Logon) | Some(EventKind::LogonResume) => {
+                load.logons += 1;
+                if let Some(ac_id) = ac_id {
+                    load.logged_on.insert(ac_id, (icao.clone(), now));
+                }
+            }
+            Some(EventKind::LogonDenied) => load.logon_denials += 1,
+            Some(EventKind::Logoff) => {
+                load.logoffs += 1;
+                load.logged_on.remove(&aircraft.id);
+            }
+            None => {}
+        }
+        load.aircraft = load.logged_on.len();
+
+        let event = Event {
+            ts: now,
+            event: kind?,
+            gs: load.id,
+            gs_name: load.name.clone(),
+            freq: msg.hfdl.freq_khz(),
+            ac_id,
+            icao,
+        };
+
+        info!(
+            "Event {:?}: aircraft {} on GS {} ({} kHz), {} logged on",
+            event.event,
+            event
+                .icao
+                .clone()
+                .or(event.ac_id.map(|id| format!("#{}", id)))
+                .unwrap_or_default(),
+            event.gs_name.as_deref().unwrap_or("?"),
+            event.freq,
+            load.aircraft,
+        );
+
+        Some(event)
+    }
+
+    pub fn snapshot(&self) -> Value {
+        serde_json::to_value(self.stations.values().collect::<Vec<&GsLoad>>())
+            .unwrap_or(Value::Null)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::fs;
+
+    /// Kind, aircraft id, ICAO address and frequency of an event
+    type Summary<'a> = (EventKind, Option<u8>, Option<&'a str>, u32);
+
+    /// A logon, a performance report and a logoff of one aircraft, then a
+    /// resumed logon of another and a denied logon of a third
+    fn fixture() -> Vec<MessageFrame> {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/testing/logons.jsonl");
+        fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(|line| MessageFrame::from_value(&serde_json::from_str(line).unwrap()).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn derives_every_event_kind() {
+        let mut table = LoadTable::new(Duration::from_secs(3600));
+        let events: Vec<Option<Event>> = fixture().iter().map(|msg| table.update(msg)).collect();
+
+        let summary: Vec<Option<Summary>> = events
+            .iter()
+            .map(|event| {
+                event
+                    .as_ref()
+                    .map(|e| (e.event, e.ac_id, e.icao.as_deref(), e.freq))
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                Some((EventKind::LogonRequest, Some(0), Some("A1B2C3"), 13276)),
+                Some((EventKind::Logon, Some(12), Some("A1B2C3"), 13276)),
+                None,
+                // The logoff only names the aircraft id, the ICAO address is
+                // remembered from its logon
+                Some((EventKind::Logoff, Some(12), Some("A1B2C3"), 13276)),
+                Some((EventKind::LogonResume, Some(34), Some("ABCDEF"), 13312)),
+                Some((EventKind::LogonDenied, Some(0), Some("C0FFEE"), 13312)),
+            ]
+        );
+
+        let event = events[1].as_ref().unwrap();
+        assert_eq!(event.gs, 1);
+        assert_eq!(event.gs_name.as_deref(), Some("San Francisco, California"));
+        assert_eq!(
+            serde_json::to_value(event).unwrap()["event"],
+            json!("logon")
+        );
+    }
+
+    #[test]
+    fn counts_ground_station_load() {
+        let mut table = LoadTable::new(Duration::from_secs(3600));
+        for msg in fixture().iter() {
+            table.update(msg);
+        }
+
+        let load = &table.snapshot()[0];
+        assert_eq!(load["id"], json!(1));
+        assert_eq!(load["aircraft"], json!(1));
+        assert_eq!(load["logon_requests"], json!(1));
+        assert_eq!(load["logons"], json!(2));
+        assert_eq!(load["logon_denials"], json!(1));
+        assert_eq!(load["logoffs"], json!(1));
+        assert_eq!(load["frames"], json!(6));
+        assert_eq!(load["performance_reports"], json!(1));
+    }
+
+    #[test]
+    fn silent_aircraft_expire() {
+        let mut table = LoadTable::new(Duration::ZERO);
+        let frames = fixture();
+        table.update(&frames[1]);
+        assert_eq!(table.snapshot()[0]["aircraft"], json!(1));
+
+        table.update(&frames[2]);
+        assert_eq!(table.snapshot()[0]["aircraft"], json!(0));
+    }
+
+    #[test]
+    fn ignores_frames_without_a_ground_station() {
+        let mut table = LoadTable::new(Duration::from_secs(3600));
+        let frame = json!({"hfdl": {"freq": 13276000, "lpdu": {
+            "err": false,
+            "src": {"type": "Aircraft", "id": 12},
+            "dst": {"type": "Aircraft", "id": 13},
+            "type": {"id": 63, "name": "Logoff request"}
+        }}});
+
+        assert!(table
+            .update(&MessageFrame::from_value(&frame).unwrap())
+            .is_none());
+        assert_eq!(table.snapshot(), json!([]));
+    }
+}
@@ -0,0 +1,358 @@
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::fs;
+
+use crate::args::{ExportArgs, ExportFormat};
+use crate::config::{GroundStation, HFDLInfo};
+use crate::tracks::{self, TrackPoint};
+
+/// Track log points grouped by ICAO address, in time order
+fn group_tracks(mut points: Vec<TrackPoint>) -> BTreeMap<String, Vec<TrackPoint>> {
+    points.sort_by_key(|point| point.ts);
+
+    let mut tracks: BTreeMap<String, Vec<TrackPoint>> = BTreeMap::new();
+    for point in points.into_iter() {
+        tracks.entry(point.icao.clone()).or_default().push(point);
+    }
+
+    tracks
+}
+
+/// Most recently reported value of an optional field along a track
+fn latest(track: &[TrackPoint], field: fn(&TrackPoint) -> &Option<String>) -> &str {
+    track
+        .iter()
+        .rev()
+        .find_map(|point| field(point).as_deref())
+        .unwrap_or("")
+}
+
+/// Splits a track into lines wherever it crosses the antimeridian, so maps
+/// do not draw a line across the whole globe. Each piece ends on ±180° at the
+/// latitude interpolated for the crossing.
+fn segments(track: &[TrackPoint]) -> Vec<Vec<(f64, f64)>> {
+    let mut segments: Vec<Vec<(f64, f64)>> = vec![];
+    let mut current: Vec<(f64, f64)> = vec![];
+
+    for point in track.iter() {
+        let (lon, lat) = (point.lon, point.lat);
+        if let Some(&(prev_lon, prev_lat)) = current.last() {
+            let edge = match lon - prev_lon {
+                d if d < -180.0 => Some((180.0, lon + 360.0)),
+                d if d > 180.0 => Some((-180.0, lon - 360.0)),
+                _ => None,
+            };
+
+            if let Some((edge, unwrapped)) = edge {
+                let t = (edge - prev_lon) / (unwrapped - prev_lon);
+                let crossing = prev_lat + t * (lat - prev_lat);
+                current.push((edge, crossing));
+                segments.push(std::mem::take(&mut current));
+                current.push((-edge, crossing));
+            }
+        }
+        current.push((lon, lat));
+    }
+    segments.push(current);
+
+    segments
+}
+
+fn stations(info: &HFDLInfo) -> Vec<&GroundStation> {
+    let mut stations: Vec<&GroundStation> = info.stations.values().collect();
+    stations.sort_by_key(|station| station.id);
+    stations
+}
+
+fn geojson(info: &HFDLInfo, tracks: &BTreeMap<String, Vec<TrackPoint>>) -> String {
+    let mut features: Vec<Value> = stations(info)
+        .into_iter()
+        .map(|station| {
+            json!({
+                "type": "Feature",
+                "geometry": { "type": "Point", "coordinates": [station.lon, station.lat] },
+                "properties": {
+                    "kind": "ground_station",
+                    "id": station.id,
+                    "name": station.name,
+                    "frequencies": station.frequencies,
+                },
+            })
+        })
+        .collect();
+
+    for (icao, track) in tracks.iter() {
+        let lines: Vec<Vec<[f64; 2]>> = segments(track)
+            .into_iter()
+            .map(|line| line.into_iter().map(|(lon, lat)| [lon, lat]).collect())
+            .collect();
+        let geometry = match lines.as_slice() {
+            [line] if line.len() == 1 => json!({ "type": "Point", "coordinates": line[0] }),
+            [line] => json!({ "type": "LineString", "coordinates": line }),
+            _ => json!({ "type": "MultiLineString", "coordinates": lines }),
+        };
+
+        features.push(json!({
+            "type": "Feature",
+            "geometry": geometry,
+            "properties": {
+                "kind": "aircraft",
+                "icao": icao,
+                "flight_id": latest(track, |p| &p.flight_id),
+                "registration": latest(track, |p| &p.registration),
+                "first_seen": track.first().map(|p| p.ts),
+                "last_seen": track.last().map(|p| p.ts),
+                "positions": track.len(),
+            },
+        }));
+    }
+
+    json!({ "type": "FeatureCollection", "features": features }).to_string()
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn kml(info: &HFDLInfo, tracks: &BTreeMap<String, Vec<TrackPoint>>) -> String {
+    let mut out = String::new();
+
+    out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    out.push_str("<kml xmlns=\"http://www.opengis.net/kml/2.2\">\n<Document>\n");
+    out.push_str("<name>viper</name>\n");
+
+    out.push_str("<Folder>\n<name>Ground Stations</name>\n");
+    for station in stations(info) {
+        let _ = writeln!(
+            out,
+            "<Placemark><name>{}</name><description>id {} {:?} kHz</description><Point><coordinates>{},{}</coordinates></Point></Placemark>",
+            escape(&station.name),
+            station.id,
+            station.frequencies,
+            station.lon,
+            station.lat
+        );
+    }
+    out.push_str("</Folder>\n");
+
+    out.push_str("<Folder>\n<name>Aircraft</name>\n");
+    for (icao, track) in tracks.iter() {
+        let flight = latest(track, |p| &p.flight_id);
+        let name = if flight.is_empty() {
+            icao.as_str()
+        } else {
+            flight
+        };
+        let lines: Vec<String> = segments(track)
+            .into_iter()
+            .map(|line| {
+                let coordinates: Vec<String> = line
+                    .iter()
+                    .map(|(lon, lat)| format!("{},{}", lon, lat))
+                    .collect();
+                coordinates.join(" ")
+            })
+            .collect();
+        let line_string = |coordinates: &String| {
+            format!(
+                "<LineString><tessellate>1</tessellate><coordinates>{}</coordinates></LineString>",
+                coordinates
+            )
+        };
+
+        let geometry = match lines.as_slice() {
+            [line] if track.len() == 1 => {
+                format!("<Point><coordinates>{}</coordinates></Point>", line)
+            }
+            [line] => line_string(line),
+            _ => format!(
+                "<MultiGeometry>{}</MultiGeometry>",
+                lines.iter().map(line_string).collect::<String>()
+            ),
+        };
+
+        let _ = writeln!(
+            out,
+            "<Placemark><name>{}</name><description>ICAO {} {}</description>{}</Placemark>",
+            escape(name),
+            escape(icao),
+            escape(latest(track, |p| &p.registration)),
+            geometry
+        );
+    }
+    out.push_str("</Folder>\n");
+
+    out.push_str("</Document>\n</kml>\n");
+    out
+}
+
+/// Runs `viper export`, writing ground stations and aircraft tracks as
+/// GeoJSON or KML
+pub fn run(info: &HFDLInfo, args: &ExportArgs) -> Result<(), String> {
+    let tracks = match &args.tracks {
+        Some(path) => group_tracks(tracks::read(path)?),
+        None => BTreeMap::new(),
+    };
+
+    let document = match args.format {
+        ExportFormat::Geojson => geojson(info, &tracks),
+        ExportFormat::Kml => kml(info, &tracks),
+    };
+
+    match &args.output {
+        Some(path) => fs::write(path, document)
+            .map_err(|e| format!("Unable to write export to {:?}: {}", path, e)),
+        None => {
+            println!("{}", document);
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use std::path::PathBuf;
+
+    fn systable() -> HFDLInfo {
+        let path = PathBuf::from(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/testing/systable.json"
+        ));
+        Config::parse_systable(&path).unwrap()
+    }
+
+    fn point(icao: &str, ts: u64, lat: f64, lon: f64) -> TrackPoint {
+        TrackPoint {
+            ts,
+            icao: icao.to_string(),
+            flight_id: None,
+            registration: None,
+            lat,
+            lon,
+            altitude: None,
+            gs: 1,
+            freq: 13276,
+        }
+    }
+
+    fn aircraft(document: &Value, icao: &str) -> Value {
+        document["features"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|f| f["properties"]["icao"] == json!(icao))
+            .unwrap()["geometry"]
+            .clone()
+    }
+
+    #[test]
+    fn groups_points_by_aircraft_in_time_order() {
+        let mut first = point("a1b2c3", 30, 10.0, 10.0);
+        first.flight_id = Some("UAL123".to_string());
+        let tracks = group_tracks(vec![
+            point("a1b2c3", 40, 12.0, 12.0),
+            point("c0ffee", 20, 0.0, 0.0),
+            first,
+            point("a1b2c3", 10, 8.0, 8.0),
+        ]);
+
+        let timestamps: Vec<u64> = tracks["a1b2c3"].iter().map(|p| p.ts).collect();
+        assert_eq!(timestamps, vec![10, 30, 40]);
+        assert_eq!(tracks["c0ffee"].len(), 1);
+        assert_eq!(latest(&tracks["a1b2c3"], |p| &p.flight_id), "UAL123");
+        assert_eq!(latest(&tracks["c0ffee"], |p| &p.flight_id), "");
+    }
+
+    #[test]
+    fn splits_tracks_at_the_antimeridian() {
+        let track = [point("a", 0, 20.0, -150.0), point("a", 1, 21.0, -160.0)];
+        assert_eq!(segments(&track).len(), 1);
+
+        // Eastbound from 170°E to 170°W crosses halfway
+        let track = [
+            point("a", 0, 10.0, 160.0),
+            point("a", 1, 20.0, 170.0),
+            point("a", 2, 30.0, -170.0),
+        ];
+        assert_eq!(
+            segments(&track),
+            vec![
+                vec![(160.0, 10.0), (170.0, 20.0), (180.0, 25.0)],
+                vec![(-180.0, 25.0), (-170.0, 30.0)],
+            ]
+        );
+
+        // Westbound, back and forth
+        let track = [
+            point("a", 0, 0.0, -175.0),
+            point("a", 1, 30.0, 175.0),
+            point("a", 2, 0.0, -175.0),
+        ];
+        assert_eq!(
+            segments(&track),
+            vec![
+                vec![(-175.0, 0.0), (-180.0, 15.0)],
+                vec![(180.0, 15.0), (175.0, 30.0), (180.0, 15.0)],
+                vec![(-180.0, 15.0), (-175.0, 0.0)],
+            ]
+        );
+    }
+
+    #[test]
+    fn geojson_geometries() {
+        let tracks = group_tracks(vec![
+            point("single", 0, 37.6, -122.4),
+            point("line", 0, 20.0, -150.0),
+            point("line", 1, 21.0, -155.0),
+            point("pacific", 0, 20.0, 170.0),
+            point("pacific", 1, 30.0, -170.0),
+        ]);
+        let document: Value = serde_json::from_str(&geojson(&systable(), &tracks)).unwrap();
+
+        let stations = document["features"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter(|f| f["properties"]["kind"] == json!("ground_station"))
+            .count();
+        assert_eq!(stations, systable().stations.len());
+
+        assert_eq!(
+            aircraft(&document, "single"),
+            json!({"type": "Point", "coordinates": [-122.4, 37.6]})
+        );
+        assert_eq!(aircraft(&document, "line")["type"], json!("LineString"));
+        assert_eq!(
+            aircraft(&document, "pacific"),
+            json!({"type": "MultiLineString", "coordinates": [
+                [[170.0, 20.0], [180.0, 25.0]],
+                [[-180.0, 25.0], [-170.0, 30.0]],
+            ]})
+        );
+    }
+
+    #[test]
+    fn kml_geometries() {
+        let mut pacific = point("pacific", 0, 20.0, 170.0);
+        pacific.flight_id = Some("A&B".to_string());
+        let tracks = group_tracks(vec![
+            point("single", 0, 37.6, -122.4),
+            pacific,
+            point("pacific", 1, 30.0, -170.0),
+        ]);
+        let document = kml(&systable(), &tracks);
+
+        assert!(document.contains("<name>A&amp;B</name>"));
+        assert!(document.contains("<Point><coordinates>-122.4,37.6</coordinates></Point>"));
+        assert!(document.contains(
+            "<MultiGeometry><LineString><tessellate>1</tessellate><coordinates>170,20 180,25</coordinates></LineString>\
+             <LineString><tessellate>1</tessellate><coordinates>-180,25 -170,30</coordinates></LineString></MultiGeometry>"
+        ));
+    }
+}
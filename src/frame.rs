@@ -0,0 +1,200 @@
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+pub const GROUND_STATION_TYPE: &str = "Ground station";
+pub const AIRCRAFT_TYPE: &str = "Aircraft";
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug)]
+pub struct Frequency {
+    pub id: u8,
+    pub freq: f64,
+}
+
+impl Frequency {
+    pub fn khz(&self) -> u32 {
+        self.freq.round() as u32
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug)]
+pub struct PduType {
+    pub id: u32,
+    pub name: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct AcInfo {
+    pub icao: String,
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug)]
+pub struct Entity {
+    pub id: u8,
+
+    #[serde(alias = "type")]
+    pub entity_type: String,
+
+    #[serde(alias = "name")]
+    pub entity_name: Option<String>,
+
+    /// Only present on aircraft dumphfdl has seen log on
+    pub ac_info: Option<AcInfo>,
+}
+
+impl Entity {
+    pub fn is_ground_station(&self) -> bool {
+        self.entity_type == GROUND_STATION_TYPE
+    }
+
+    pub fn is_aircraft(&self) -> bool {
+        self.entity_type == AIRCRAFT_TYPE
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Position {
+    pub lat: f64,
+    pub lon: f64,
+}
+
+impl Position {
+    pub fn is_valid(&self) -> bool {
+        (-90.0..=90.0).contains(&self.lat) && (-180.0..=180.0).contains(&self.lon)
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug)]
+pub struct Acars {
+    pub mode: Option<String>,
+    pub label: Option<String>,
+    pub sublabel: Option<String>,
+    pub blk_id: Option<String>,
+    pub msg_num: Option<String>,
+    pub flight: Option<String>,
+    pub reg: Option<String>,
+    pub msg_text: Option<String>,
+}
+
+impl Acars {
+    /// Registration without the padding dots ACARS prepends to short tails
+    pub fn registration(&self) -> Option<&str> {
+        self.reg
+            .as_deref()
+            .map(|reg| reg.trim_start_matches('.'))
+            .filter(|reg| !reg.is_empty())
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Hfnpdu {
+    #[serde(rename = "type")]
+    pub kind: Option<PduType>,
+
+    pub flight_id: Option<String>,
+    pub pos: Option<Position>,
+    pub acars: Option<Acars>,
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug)]
+pub struct GroundStation {
+    pub gs: Entity,
+    pub utc_sync: bool,
+    pub freqs: Vec<Frequency>,
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug)]
+pub struct Lpdu {
+    pub err: bool,
+    pub src: Entity,
+    pub dst: Entity,
+
+    #[serde(rename = "type")]
+    pub kind: Option<PduType>,
+
+    /// Logon confirm fields pairing an ICAO address with its aircraft id
+    pub ac_info: Option<AcInfo>,
+    pub assigned_ac_id: Option<u8>,
+
+    pub hfnpdu: Option<Hfnpdu>,
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug)]
+pub struct Spdu {
+    pub err: bool,
+    pub src: Entity,
+    pub gs_status: Vec<GroundStation>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Hfdl {
+    pub freq: u32,
+    pub freq_skew: Option<f64>,
+    pub sig_level: Option<f64>,
+    pub noise_level: Option<f64>,
+    pub spdu: Option<Spdu>,
+    pub lpdu: Option<Lpdu>,
+}
+
+impl Hfdl {
+    pub fn freq_khz(&self) -> u32 {
+        self.freq / 1000
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct MessageFrame {
+    pub hfdl: Hfdl,
+}
+
+/// Frames that failed CRC are kept by dumphfdl with only a partial PDU, so the
+/// error flags are read straight from the JSON rather than a `MessageFrame`
+pub fn has_error(frame: &Value) -> bool {
+    ["/hfdl/lpdu/err", "/hfdl/spdu/err", "/hfdl/lpdu/hfnpdu/err"]
+        .iter()
+        .any(|ptr| frame.pointer(ptr).and_then(Value::as_bool) == Some(true))
+}
+
+/// Adds `value` under `key` in the frame's `viper` object, which collects
+/// everything viper adds to dumphfdl's output
+pub fn annotate(frame: &mut Value, key: &str, value: Value) {
+    let fields = match frame.as_object_mut() {
+        Some(fields) => fields,
+        None => return,
+    };
+
+    let viper = fields
+        .entry("viper")
+        .or_insert_with(|| Value::Object(Map::new()));
+    if let Some(viper) = viper.as_object_mut() {
+        viper.insert(key.to_string(), value);
+    }
+}
+
+impl MessageFrame {
+    /// The ground station a frame was sent by or addressed to
+    pub fn ground_station(&self) -> Option<&Entity> {
+        match (&self.hfdl.spdu, &self.hfdl.lpdu) {
+            (Some(spdu), _) => Some(&spdu.src),
+            (None, Some(lpdu)) => [&lpdu.src, &lpdu.dst]
+                .into_iter()
+                .find(|entity| entity.is_ground_station()),
+            (None, None) => None,
+        }
+    }
+
+    pub fn acars(&self) -> Option<&Acars> {
+        self.hfdl.lpdu.as_ref()?.hfnpdu.as_ref()?.acars.as_ref()
+    }
+
+    pub fn from_value(frame: &Value) -> Result<MessageFrame, String> {
+        serde_json::from_value(frame.clone())
+            .map_err(|e| format!("Failed to coerce frame into MessageFrame: {}", e))
+    }
+}
@@ -0,0 +1,256 @@
+use log::*;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::btree_map::Entry;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::PathBuf;
+
+use crate::frame::MessageFrame;
+
+const MIN_SAMPLES: usize = 10;
+const GAIN_STEP: f64 = 3.0;
+const MIN_GAIN: f64 = 0.0;
+const MAX_GAIN: f64 = 48.0;
+const DEFAULT_START_GAIN: f64 = 20.0;
+
+/// Noise floor window (dBFS): below it the SDR's own noise dominates, above
+/// it strong signals risk overloading the ADC
+const NOISE_FLOOR_LOW: f64 = -55.0;
+const NOISE_FLOOR_HIGH: f64 = -40.0;
+
+/// Drop in SNR (dB) after a gain change that causes it to be reverted
+const SNR_REVERT_THRESHOLD: f64 = 1.0;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct BandGain {
+    pub gain: f64,
+    pub noise_floor: Option<f64>,
+    pub snr: Option<f64>,
+
+    /// Gain and SNR before the last adjustment, used to revert bad changes
+    previous: Option<(f64, f64)>,
+}
+
+#[derive(Default)]
+struct Samples {
+    noise: Vec<f64>,
+    snr: Vec<f64>,
+}
+
+fn median(values: &mut [f64]) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+
+    values.sort_by(|a, b| a.total_cmp(b));
+    Some(values[values.len() / 2])
+}
+
+/// Learns a gain per band between sessions by keeping the noise floor inside
+/// a fixed window and reverting changes that made the SNR worse
+pub struct GainControl {
+    path: Option<PathBuf>,
+    bands: BTreeMap<u32, BandGain>,
+    samples: HashMap<u32, Samples>,
+}
+
+impl GainControl {
+    pub fn new(path: Option<PathBuf>) -> Result<Self, String> {
+        let bands = match &path {
+            Some(path) if path.exists() => {
+                let contents = fs::read_to_string(path)
+                    .map_err(|e| format!("Unable to read gain file {:?}: {}", path, e))?;
+                serde_json::from_str(&contents)
+                    .map_err(|e| format!("Unable to deserialize gain file {:?}: {}", path, e))?
+            }
+            _ => BTreeMap::new(),
+        };
+
+        Ok(GainControl {
+            path,
+            bands,
+            samples: HashMap::new(),
+        })
+    }
+
+    pub fn gain_for(&self, band: u32) -> Option<f64> {
+        self.bands.get(&band).map(|b| b.gain)
+    }
+
+    pub fn update(&mut self, band: u32, msg: &MessageFrame) {
+        if let (Some(sig), Some(noise)) = (msg.hfdl.sig_level, msg.hfdl.noise_level) {
+            let samples = self.samples.entry(band).or_default();
+            samples.noise.push(noise);
+            samples.snr.push(sig - noise);
+        }
+    }
+
+    /// Adjusts the gain of every band heard this session. `in_effect` maps
+    /// bands to the gain they were sampled with (`None` for autogain).
+    pub fn end_session(&mut self, in_effect: &HashMap<u32, Option<f64>>) {
+        let mut changed = false;
+
+        for (band, mut samples) in self.samples.drain() {
+            if samples.noise.len() < MIN_SAMPLES {
+                continue;
+            }
+
+            let (noise, snr) = match (median(&mut samples.noise), median(&mut samples.snr)) {
+                (Some(noise), Some(snr)) => (noise, snr),
+                _ => continue,
+            };
+
+            // The gain picked by the SDR's autogain is unknown, so there is
+            // nothing to step from yet. Start from a fixed gain next launch.
+            let gain = match in_effect.get(&band).copied().flatten() {
+                Some(gain) => gain,
+                None => {
+                    if let Entry::Vacant(entry) = self.bands.entry(band) {
+                        info!(
+                            "Band {} noise floor {:.1} dBFS, SNR {:.1} dB with autogain -> next gain {} dB",
+                            band, noise, snr, DEFAULT_START_GAIN
+                        );
+                        entry.insert(BandGain {
+                            gain: DEFAULT_START_GAIN,
+                            noise_floor: Some(noise),
+                            snr: Some(snr),
+                            previous: None,
+                        });
+                        changed = true;
+                    }
+                    continue;
+                }
+            };
+            let entry = self.bands.entry(band).or_insert(BandGain {
+                gain,
+                noise_floor: None,
+                snr: None,
+                previous: None,
+            });
+
+            let next = match entry.previous {
+                Some((prev_gain, prev_snr)) if snr + SNR_REVERT_THRESHOLD < prev_snr => {
+                    info!(
+                        "Band {} SNR dropped {:.1} -> {:.1} dB at {} dB gain, reverting to {} dB",
+                        band, prev_snr, snr, gain, prev_gain
+                    );
+                    prev_gain
+                }
+                _ if noise > NOISE_FLOOR_HIGH => gain - GAIN_STEP,
+                _ if noise < NOISE_FLOOR_LOW => gain + GAIN_STEP,
+                _ => gain,
+            }
+            .clamp(MIN_GAIN, MAX_GAIN);
+
+            info!(
+                "Band {} noise floor {:.1} dBFS, SNR {:.1} dB at {} dB gain -> next gain {} dB",
+                band, noise, snr, gain, next
+            );
+
+            // A revert is final, otherwise remember how to undo the change
+            let reverted = matches!(entry.previous, Some((prev_gain, _)) if prev_gain == next);
+            entry.previous = (next != gain && !reverted).then_some((gain, snr));
+            entry.gain = next;
+            entry.noise_floor = Some(noise);
+            entry.snr = Some(snr);
+            changed = true;
+        }
+
+        if changed {
+            self.save();
+        }
+    }
+
+    fn save(&self) {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return,
+        };
+
+        let result = serde_json::to_string_pretty(&self.bands)
+            .map_err(|e| e.to_string())
+            .and_then(|contents| fs::write(path, contents).map_err(|e| e.to_string()));
+        if let Err(e) = result {
+            error!("Unable to save learned gains to {:?}: {}", path, e);
+        }
+    }
+
+    pub fn snapshot(&self) -> Value {
+        serde_json::to_value(&self.bands).unwrap_or(Value::Null)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// Runs a session of `band` at `gain` hearing frames with the given noise
+    /// floor and SNR
+    fn session(control: &mut GainControl, band: u32, gain: Option<f64>, noise: f64, snr: f64) {
+        for _ in 0..MIN_SAMPLES {
+            let frame = json!({"hfdl": {
+                "freq": 13276000,
+                "noise_level": noise,
+                "sig_level": noise + snr
+            }});
+            control.update(band, &MessageFrame::from_value(&frame).unwrap());
+        }
+        control.end_session(&HashMap::from([(band, gain)]));
+    }
+
+    #[test]
+    fn steps_gain_to_keep_noise_floor_in_window() {
+        let mut control = GainControl::new(None).unwrap();
+        session(&mut control, 5, Some(20.0), -35.0, 10.0);
+        assert_eq!(control.gain_for(5), Some(17.0));
+
+        session(&mut control, 8, Some(20.0), -60.0, 10.0);
+        assert_eq!(control.gain_for(8), Some(23.0));
+
+        session(&mut control, 13, Some(20.0), -48.0, 10.0);
+        assert_eq!(control.gain_for(13), Some(20.0));
+
+        session(&mut control, 21, Some(MAX_GAIN), -60.0, 10.0);
+        assert_eq!(control.gain_for(21), Some(MAX_GAIN));
+    }
+
+    #[test]
+    fn reverts_a_step_that_lowers_snr() {
+        let mut control = GainControl::new(None).unwrap();
+        session(&mut control, 8, Some(20.0), -60.0, 12.0);
+        assert_eq!(control.gain_for(8), Some(23.0));
+
+        // Within 1 dB is kept and stepped again
+        session(&mut control, 8, Some(23.0), -58.0, 11.5);
+        assert_eq!(control.gain_for(8), Some(26.0));
+
+        session(&mut control, 8, Some(26.0), -57.0, 10.0);
+        assert_eq!(control.gain_for(8), Some(23.0));
+
+        // The revert is not itself reverted
+        session(&mut control, 8, Some(23.0), -50.0, 8.0);
+        assert_eq!(control.gain_for(8), Some(23.0));
+    }
+
+    #[test]
+    fn autogain_sessions_only_seed_a_starting_gain() {
+        let mut control = GainControl::new(None).unwrap();
+        session(&mut control, 5, None, -30.0, 10.0);
+        assert_eq!(control.gain_for(5), Some(DEFAULT_START_GAIN));
+        assert!(control.bands[&5].previous.is_none());
+
+        session(&mut control, 5, Some(DEFAULT_START_GAIN), -30.0, 10.0);
+        assert_eq!(control.gain_for(5), Some(DEFAULT_START_GAIN - GAIN_STEP));
+    }
+
+    #[test]
+    fn needs_enough_samples() {
+        let mut control = GainControl::new(None).unwrap();
+        let frame = json!({"hfdl": {"freq": 13276000, "noise_level": -30.0, "sig_level": -20.0}});
+        control.update(5, &MessageFrame::from_value(&frame).unwrap());
+        control.end_session(&HashMap::from([(5, Some(20.0))]));
+        assert_eq!(control.gain_for(5), None);
+    }
+}
@@ -0,0 +1,215 @@
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+use crate::config::GroundStationMap;
+use crate::frame::MessageFrame;
+
+const EARTH_RADIUS_KM: f64 = 6371.0;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Location {
+    pub lat: f64,
+    pub lon: f64,
+}
+
+impl Location {
+    /// Parses `LAT,LON` in decimal degrees
+    pub fn parse(spec: &str) -> Result<Location, String> {
+        let (lat, lon) = match spec.split_once(',') {
+            Some((lat, lon)) => (lat.trim().parse::<f64>(), lon.trim().parse::<f64>()),
+            None => return Err(format!("Location is not LAT,LON: {}", spec)),
+        };
+
+        match (lat, lon) {
+            (Ok(lat), Ok(lon))
+                if (-90.0..=90.0).contains(&lat) && (-180.0..=180.0).contains(&lon) =>
+            {
+                Ok(Location { lat, lon })
+            }
+            _ => Err(format!("Location is not a valid LAT,LON: {}", spec)),
+        }
+    }
+
+    /// Great-circle distance in km
+    pub fn distance_km(&self, to: &Location) -> f64 {
+        let (lat1, lat2) = (self.lat.to_radians(), to.lat.to_radians());
+        let dlat = lat2 - lat1;
+        let dlon = (to.lon - self.lon).to_radians();
+
+        let a = (dlat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (dlon / 2.0).sin().powi(2);
+        2.0 * EARTH_RADIUS_KM * a.sqrt().min(1.0).asin()
+    }
+
+    /// Initial great-circle bearing in degrees clockwise from true north
+    pub fn bearing(&self, to: &Location) -> f64 {
+        let (lat1, lat2) = (self.lat.to_radians(), to.lat.to_radians());
+        let dlon = (to.lon - self.lon).to_radians();
+
+        let y = dlon.sin() * lat2.cos();
+        let x = lat1.cos() * lat2.sin() - lat1.sin() * lat2.cos() * dlon.cos();
+        y.atan2(x).to_degrees().rem_euclid(360.0)
+    }
+}
+
+fn round1(value: f64) -> f64 {
+    (value * 10.0).round() / 10.0
+}
+
+/// Describes the ground station of every frame from the system table and,
+/// given the receiver's location, how far away and in which direction it is
+pub struct StationLocator {
+    stations: HashMap<u32, (String, Location)>,
+    receiver: Option<Location>,
+}
+
+impl StationLocator {
+    pub fn new(stations: &GroundStationMap, receiver: Option<Location>) -> Self {
+        StationLocator {
+            stations: stations
+                .values()
+                .map(|station| {
+                    let location = Location {
+                        lat: station.lat,
+                        lon: station.lon,
+                    };
+                    (station.id, (station.name.clone(), location))
+                })
+                .collect(),
+            receiver,
+        }
+    }
+
+    pub fn describe(&self, msg: &MessageFrame) -> Option<Value> {
+        let gs = msg.ground_station()?;
+        let (name, location) = self.stations.get(&(gs.id as u32))?;
+
+        let mut info = json!({
+            "id": gs.id,
+            "name": name,
+            "lat": location.lat,
+            "lon": location.lon,
+        });
+        if let Some(receiver) = &self.receiver {
+            info["distance_km"] = json!(round1(receiver.distance_km(location)));
+            info["bearing"] = json!(round1(receiver.bearing(location)));
+        }
+
+        Some(info)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LONDON: Location = Location {
+        lat: 51.5074,
+        lon: -0.1278,
+    };
+    const PARIS: Location = Location {
+        lat: 48.8566,
+        lon: 2.3522,
+    };
+    const NEW_YORK: Location = Location {
+        lat: 40.7128,
+        lon: -74.0060,
+    };
+    const SAN_FRANCISCO: Location = Location {
+        lat: 37.6213,
+        lon: -122.3790,
+    };
+    const SYDNEY: Location = Location {
+        lat: -33.9399,
+        lon: 151.1753,
+    };
+
+    fn assert_near(value: f64, expected: f64, tolerance: f64) {
+        assert!(
+            (value - expected).abs() <= tolerance,
+            "{} is not within {} of {}",
+            value,
+            tolerance,
+            expected
+        );
+    }
+
+    #[test]
+    fn distances_between_cities() {
+        assert_near(LONDON.distance_km(&PARIS), 344.0, 3.0);
+        assert_near(NEW_YORK.distance_km(&LONDON), 5570.0, 20.0);
+        // Crosses the antimeridian
+        assert_near(SAN_FRANCISCO.distance_km(&SYDNEY), 11950.0, 40.0);
+
+        assert_eq!(PARIS.distance_km(&LONDON), LONDON.distance_km(&PARIS));
+        assert_eq!(LONDON.distance_km(&LONDON), 0.0);
+    }
+
+    #[test]
+    fn distance_to_the_antipode_is_half_the_circumference() {
+        let antipode = Location {
+            lat: -LONDON.lat,
+            lon: LONDON.lon + 180.0,
+        };
+        assert_near(
+            LONDON.distance_km(&antipode),
+            std::f64::consts::PI * EARTH_RADIUS_KM,
+            1e-6,
+        );
+    }
+
+    #[test]
+    fn bearings_between_cities() {
+        assert_near(LONDON.bearing(&PARIS), 148.1, 0.1);
+        assert_near(PARIS.bearing(&LONDON), 330.0, 0.1);
+        assert_near(NEW_YORK.bearing(&LONDON), 51.2, 0.1);
+        assert_near(LONDON.bearing(&NEW_YORK), 288.3, 0.1);
+        assert_near(SAN_FRANCISCO.bearing(&SYDNEY), 240.2, 0.1);
+    }
+
+    #[test]
+    fn bearings_along_the_axes() {
+        let origin = Location { lat: 0.0, lon: 0.0 };
+        let bearing = |lat, lon| origin.bearing(&Location { lat, lon });
+
+        assert_near(bearing(10.0, 0.0), 0.0, 1e-9);
+        assert_near(bearing(0.0, 10.0), 90.0, 1e-9);
+        assert_near(bearing(-10.0, 0.0), 180.0, 1e-9);
+        assert_near(bearing(0.0, -10.0), 270.0, 1e-9);
+    }
+
+    #[test]
+    fn parses_locations() {
+        let location = Location::parse("51.5074,-0.1278").unwrap();
+        assert_eq!((location.lat, location.lon), (51.5074, -0.1278));
+
+        let location = Location::parse(" -33.94 , 151.18 ").unwrap();
+        assert_eq!((location.lat, location.lon), (-33.94, 151.18));
+
+        let location = Location::parse("90,-180").unwrap();
+        assert_eq!((location.lat, location.lon), (90.0, -180.0));
+    }
+
+    #[test]
+    fn rejects_malformed_locations() {
+        for spec in [
+            "",
+            "51.5",
+            "51.5;-0.1",
+            "north,west",
+            "51.5,",
+            ",-0.1",
+            "51.5,-0.1,10",
+            "90.1,0",
+            "-91,0",
+            "0,180.5",
+            "0,-181",
+            "NaN,0",
+        ] {
+            assert!(
+                Location::parse(spec).is_err(),
+                "{:?} should not parse",
+                spec
+            );
+        }
+    }
+}
@@ -0,0 +1,264 @@
+use log::*;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+
+use crate::config::{FrequencyBandMap, GroundStationMap};
+use crate::frame::{Entity, MessageFrame};
+use crate::util::unix_now;
+
+#[derive(Serialize, Clone, Debug)]
+pub struct StationStatus {
+    pub id: u32,
+    pub name: String,
+
+    /// Last time a frame to/from the station was heard directly
+    pub last_heard: Option<u64>,
+    /// Band the station was last heard directly on
+    pub band: Option<u32>,
+
+    /// Last time a squitter reported on the station
+    pub status_updated: Option<u64>,
+    pub utc_sync: Option<bool>,
+    pub active_freqs: Vec<u32>,
+}
+
+/// Per ground station state assembled from squitters and link-layer traffic.
+/// Frequencies that any owning station has not been reported on recently are
+/// assumed to be active.
+pub struct GroundStationTable {
+    expiry: Duration,
+    /// Ground stations transmitting on every frequency; several stations can
+    /// share one
+    owners: HashMap<u32, Vec<u32>>,
+    stations: BTreeMap<u32, StationStatus>,
+}
+
+impl GroundStationTable {
+    pub fn new(stations: &GroundStationMap, expiry: Duration) -> Self {
+        let mut table = GroundStationTable {
+            expiry,
+            owners: HashMap::new(),
+            stations: BTreeMap::new(),
+        };
+
+        for station in stations.values() {
+            for freq in station.frequencies.iter() {
+                table.own(*freq, station.id);
+            }
+            table.station(station.id, Some(&station.name));
+        }
+
+        table
+    }
+
+    fn own(&mut self, freq: u32, gs_id: u32) {
+        let owners = self.owners.entry(freq).or_default();
+        if !owners.contains(&gs_id) {
+            owners.push(gs_id);
+        }
+    }
+
+    fn station(&mut self, id: u32, name: Option<&str>) -> &mut StationStatus {
+        self.stations.entry(id).or_insert_with(|| StationStatus {
+            id,
+            name: name.unwrap_or("Unknown").to_string(),
+            last_heard: None,
+            band: None,
+            status_updated: None,
+            utc_sync: None,
+            active_freqs: vec![],
+        })
+    }
+
+    fn heard(&mut self, entity: &Entity, band: u32, now: u64) {
+        if !entity.is_ground_station() {
+            return;
+        }
+
+        let station = self.station(entity.id as u32, entity.entity_name.as_deref());
+        station.last_heard = Some(now);
+        station.band = Some(band);
+    }
+
+    pub fn update(&mut self, msg: &MessageFrame, band: u32) {
+        let now = unix_now();
+
+        if let Some(spdu) = &msg.hfdl.spdu {
+            self.heard(&spdu.src, band, now);
+
+            for gs in spdu.gs_status.iter() {
+                let gs_id = gs.gs.id as u32;
+                let mut freqs: Vec<u32> = gs.freqs.iter().map(|f| f.khz()).collect();
+                freqs.sort_unstable();
+
+                // Learn ownership the system table does not list
+                for freq in freqs.iter() {
+                    self.own(*freq, gs_id);
+                }
+
+                let station = self.station(gs_id, gs.gs.entity_name.as_deref());
+                if station.status_updated.is_some() && station.active_freqs != freqs {
+                    info!(
+                        "Ground station {} active frequencies changed: {:?}",
+                        station.name, freqs
+                    );
+                }
+                if station.utc_sync == Some(true) && !gs.utc_sync {
+                    warn!("Ground station {} lost UTC sync", station.name);
+                }
+
+                station.status_updated = Some(now);
+                station.utc_sync = Some(gs.utc_sync);
+                station.active_freqs = freqs;
+            }
+        } else if let Some(lpdu) = &msg.hfdl.lpdu {
+            self.heard(&lpdu.src, band, now);
+            self.heard(&lpdu.dst, band, now);
+        }
+    }
+
+    fn is_fresh(&self, station: &StationStatus) -> bool {
+        station
+            .status_updated
+            .is_some_and(|updated| unix_now().saturating_sub(updated) < self.expiry.as_secs())
+    }
+
+    /// A frequency is inactive only once every station owning it has recently
+    /// been reported without it
+    pub fn is_active(&self, freq: u32) -> bool {
+        let owners = match self.owners.get(&freq) {
+            Some(owners) => owners,
+            None => return true,
+        };
+
+        owners.iter().any(|id| match self.stations.get(id) {
+            Some(station) => !self.is_fresh(station) || station.active_freqs.contains(&freq),
+            None => true,
+        })
+    }
+
+    /// Returns the bands that have at least one frequency that may be active
+    pub fn filter(&self, bands: &FrequencyBandMap) -> FrequencyBandMap {
+        bands
+            .iter()
+            .filter(|(_, freqs)| freqs.iter().any(|f| self.is_active(*f)))
+            .map(|(band, freqs)| (*band, freqs.clone()))
+            .collect()
+    }
+
+    pub fn snapshot(&self) -> Value {
+        serde_json::to_value(self.stations.values().collect::<Vec<&StationStatus>>())
+            .unwrap_or(Value::Null)
+    }
+
+    pub fn log_summary(&self) {
+        let now = unix_now();
+        let ago = |ts: Option<u64>| {
+            ts.map_or("never".to_string(), |t| {
+                format!("{}s ago", now.saturating_sub(t))
+            })
+        };
+
+        info!("Ground station status summary:");
+        for station in self.stations.values() {
+            let state = match (self.is_fresh(station), station.active_freqs.is_empty()) {
+                (false, _) => "UNKNOWN",
+                (true, true) => "DOWN",
+                (true, false) => "UP",
+            };
+
+            info!(
+                "  {:>3} {:<40} {:<7} utc_sync={:<5} heard={} band={} freqs={:?}",
+                station.id,
+                station.name,
+                state,
+                station.utc_sync.map_or("?".to_string(), |s| s.to_string()),
+                ago(station.last_heard),
+                station.band.map_or("-".to_string(), |b| b.to_string()),
+                station.active_freqs,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use serde_json::json;
+    use std::path::PathBuf;
+
+    fn table() -> GroundStationTable {
+        let path = PathBuf::from(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/testing/systable.json"
+        ));
+        let info = Config::parse_systable(&path).unwrap();
+        GroundStationTable::new(&info.stations, Duration::from_secs(600))
+    }
+
+    /// Squitter from San Francisco reporting the active frequencies of `gs_id`
+    fn squitter(gs_id: u32, freqs: &[u32]) -> MessageFrame {
+        let freqs: Vec<Value> = freqs
+            .iter()
+            .map(|f| json!({"id": 0, "freq": *f as f64}))
+            .collect();
+        let frame = json!({"hfdl": {"freq": 13276000, "spdu": {
+            "err": false,
+            "src": {"type": "Ground station", "id": 1, "name": "San Francisco, California"},
+            "gs_status": [{
+                "gs": {"type": "Ground station", "id": gs_id},
+                "utc_sync": true,
+                "freqs": freqs
+            }]
+        }}});
+        MessageFrame::from_value(&frame).unwrap()
+    }
+
+    #[test]
+    fn shared_frequency_stays_active_while_any_owner_may_use_it() {
+        let mut table = table();
+        // 17919 kHz is shared by San Francisco, Molokai, Riverhead, Barrow and Agana
+        let owners = [1, 2, 4, 9, 16];
+        assert_eq!(table.owners[&17919].len(), owners.len());
+        assert!(table.is_active(17919));
+
+        for id in owners.iter().take(4) {
+            table.update(&squitter(*id, &[21934]), 17);
+            assert!(table.is_active(17919), "inactive after station {}", id);
+        }
+
+        table.update(&squitter(16, &[21928]), 17);
+        assert!(!table.is_active(17919));
+
+        table.update(&squitter(9, &[17919]), 17);
+        assert!(table.is_active(17919));
+    }
+
+    #[test]
+    fn squitters_teach_unknown_ownership() {
+        let mut table = table();
+        assert!(table.is_active(12345));
+
+        table.update(&squitter(7, &[12345]), 13);
+        table.update(&squitter(7, &[8927]), 13);
+        assert!(!table.is_active(12345));
+        assert_eq!(table.owners[&8927].len(), 4);
+    }
+
+    #[test]
+    fn bands_with_no_active_frequency_are_filtered() {
+        let mut table = table();
+        let bands = FrequencyBandMap::from([(15, vec![15025]), (21, vec![21934])]);
+        assert_eq!(table.filter(&bands).len(), 2);
+
+        // 15025 kHz is owned by Reykjavik only
+        table.update(&squitter(3, &[11184]), 11);
+        assert_eq!(
+            table.filter(&bands),
+            FrequencyBandMap::from([(21, vec![21934])])
+        );
+    }
+}
@@ -0,0 +1,36 @@
+use serde::Serialize;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Append-only JSON lines file
+pub struct JsonLog {
+    path: PathBuf,
+    file: File,
+}
+
+impl JsonLog {
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| format!("Unable to open {:?}: {}", path, e))?;
+
+        Ok(JsonLog {
+            path: path.to_path_buf(),
+            file,
+        })
+    }
+
+    pub fn write<T: Serialize>(&mut self, entry: &T) -> Result<(), String> {
+        let line = serde_json::to_string(entry).map_err(|e| e.to_string())?;
+        writeln!(self.file, "{}", line)
+            .map_err(|e| format!("Unable to write to {:?}: {}", self.path, e))
+    }
+}
+
+/// Opens the log at `path` if one was configured
+pub fn open(path: &Option<PathBuf>) -> Result<Option<JsonLog>, String> {
+    path.as_deref().map(JsonLog::open).transpose()
+}
@@ -1,17 +1,51 @@
-use crossbeam::channel::{after, bounded, select};
-use serde_json::Value;
-use std::io::{BufRead, BufReader, Seek, SeekFrom, Write};
-use std::process::{Command, Stdio};
+use crossbeam::channel::{after, never, select, tick};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::{Seek, SeekFrom, Write};
+use std::sync::{Arc, Mutex};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tempfile::NamedTempFile;
 
 use clap::Parser;
 use log::*;
 
+use frame::MessageFrame;
+
+mod acars;
+mod aircraft;
+mod alerts;
 mod args;
+mod calibration;
 mod chooser;
+mod cluster;
 mod config;
+mod dedupe;
+mod events;
+mod export;
+mod frame;
+mod gain;
+mod geo;
+mod gs_status;
+mod http;
+mod jsonlog;
+mod output;
+mod quarantine;
+mod readsb;
+mod receiver;
+mod reference;
+mod rules;
+mod sbs;
+mod session;
+mod status;
+mod tracks;
+mod util;
+
+const DEDUPE_FLUSH_MS: u64 = 250;
+
+/// Seconds between evaluations of a long-running session, so calibration,
+/// gain and quarantine also work when the chooser never changes bands
+const EVALUATE_INTERVAL_SECS: u64 = 300;
 
 fn main() {
     let args = args::Args::parse();
@@ -28,6 +62,15 @@ fn main() {
         .init()
         .unwrap();
 
+    if let Some(args::Command::Export(export_args)) = &args.command {
+        let result = config::Config::parse_systable(&args.sys_table)
+            .and_then(|info| export::run(&info, export_args));
+        if let Err(e) = result {
+            error!("Export failed: {}", e);
+        }
+        return;
+    }
+
     let config = match config::Config::from_args(&args) {
         Ok(cfg) => cfg,
         Err(e) => {
@@ -38,7 +81,22 @@ fn main() {
     info!("Configuration demarshalled from command line arguments.");
     info!("  {}", config);
 
+    for (name, band) in config.band_names.iter() {
+        info!("Custom band {} = {:?}", name, config.info.bands[band]);
+    }
+
     let (name, props) = args.chooser_params();
+    let props = match chooser::resolve_band_names(&props, &config.band_names) {
+        Ok(props) => props,
+        Err(e) => {
+            error!("Invalid chooser props: {}", e);
+            return;
+        }
+    };
+    let props: HashMap<&str, &str> = props
+        .iter()
+        .map(|(key, value)| (key.as_str(), value.as_str()))
+        .collect();
     info!("Chooser plugin name={} props={:?}", name, props);
 
     let mut plugin = match chooser::get(name) {
@@ -61,11 +119,6 @@ fn main() {
 
     let systable_temp_path = systable.into_temp_path();
 
-    let mut addition_args: Vec<String> = vec![];
-    if let Some(output) = config.output {
-        addition_args.extend_from_slice(&["--output".to_string(), output])
-    }
-
     info!(
         "System Table information written to {:?}",
         systable_temp_path
@@ -73,117 +126,618 @@ fn main() {
     info!("Starting listening session...");
     info!("");
 
-    loop {
-        let band = match plugin.choose(&config.info.bands, &props) {
-            Ok(val) => val.clone(),
+    let gs_table = Arc::new(Mutex::new(gs_status::GroundStationTable::new(
+        &config.info.stations,
+        Duration::from_secs(config.squitter_expiry as u64),
+    )));
+
+    let reference = match reference::Reference::load(&config.aircraft_db, &config.airline_db) {
+        Ok(reference) => reference,
+        Err(e) => {
+            error!("{}", e);
+            return;
+        }
+    };
+    let aircraft_table = Arc::new(Mutex::new(aircraft::AircraftTable::new(
+        Duration::from_secs(config.aircraft_expiry as u64),
+        reference,
+    )));
+
+    if let Some(path) = &config.aircraft_json {
+        readsb::start_writer(
+            path.clone(),
+            Duration::from_secs(config.aircraft_json_interval as u64),
+            Arc::clone(&aircraft_table),
+        );
+    }
+
+    if let Some(addr) = &config.sbs_addr {
+        if let Err(e) = sbs::start(addr, Arc::clone(&aircraft_table)) {
+            error!("{}", e);
+            return;
+        }
+    }
+
+    let mut track_log = match jsonlog::open(&config.track_log) {
+        Ok(log) => log,
+        Err(e) => {
+            error!("{}", e);
+            return;
+        }
+    };
+
+    let load_table = Arc::new(Mutex::new(events::LoadTable::new(Duration::from_secs(
+        config.aircraft_expiry as u64,
+    ))));
+    let mut event_log = match jsonlog::open(&config.event_log) {
+        Ok(log) => log,
+        Err(e) => {
+            error!("{}", e);
+            return;
+        }
+    };
+
+    let gain_control = match gain::GainControl::new(config.gain_file.clone()) {
+        Ok(gain_control) => Arc::new(Mutex::new(gain_control)),
+        Err(e) => {
+            error!("{}", e);
+            return;
+        }
+    };
+
+    let quarantine = config.quarantine_cooldown.map(|secs| {
+        Arc::new(Mutex::new(quarantine::Quarantine::new(
+            Duration::from_secs(secs as u64),
+            config.quarantine_error_rate,
+            config.quarantine_noise_margin,
+        )))
+    });
+
+    let receivers_status: Arc<Mutex<Vec<receiver::ReceiverStatus>>> = Arc::new(Mutex::new(vec![]));
+
+    let cluster = match &config.cluster {
+        Some(group) => match cluster::Cluster::start(group, &config.station) {
+            Ok(cluster) => Some(cluster),
             Err(e) => {
-                error!("Failed to choose a frequency band to listen to: {}", e);
+                error!("{}", e);
                 return;
             }
-        };
-        info!("New session started: band={:?}", band);
+        },
+        None => None,
+    };
 
-        let bandwidth = match band.iter().max().unwrap_or(&0) - band.iter().min().unwrap_or(&0) {
-            d if d > 256 && d <= 384 => "384000",
-            d if d <= 256 => "256000",
-            _ => {
-                error!("Bandwidth calculation failed: {:?}", band);
-                return;
-            }
+    if let Some(addr) = &config.status_addr {
+        let mut server = status::StatusServer::new();
+        status::provide(&mut server, "/gs_status", &gs_table, |table| {
+            table.snapshot()
+        });
+        status::provide(&mut server, "/aircraft", &aircraft_table, |table| {
+            table.snapshot()
+        });
+        status::provide(&mut server, "/aircraft.json", &aircraft_table, |table| {
+            readsb::aircraft_json(table)
+        });
+        status::provide(&mut server, "/gs_load", &load_table, |table| {
+            table.snapshot()
+        });
+        status::provide(&mut server, "/receivers", &receivers_status, |receivers| {
+            serde_json::to_value(receivers).unwrap_or(Value::Null)
+        });
+        if config.auto_gain {
+            status::provide(&mut server, "/gain", &gain_control, |gain_control| {
+                gain_control.snapshot()
+            });
+        }
+        if let Some(quarantine) = &quarantine {
+            status::provide(&mut server, "/quarantine", quarantine, |quarantine| {
+                quarantine.snapshot()
+            });
+        }
+        if let Some(cluster) = &cluster {
+            status::provide(&mut server, "/cluster", &cluster.view, |view| {
+                view.snapshot()
+            });
+        }
+
+        if let Err(e) = server.serve(addr) {
+            error!("{}", e);
+            return;
+        }
+    }
+
+    let summary_ticker = match config.summary_interval {
+        0 => never(),
+        secs => tick(Duration::from_secs(secs as u64)),
+    };
+
+    let mut router = match output::Router::new(&config.rules, &config.sinks) {
+        Ok(router) => router,
+        Err(e) => {
+            error!("{}", e);
+            return;
+        }
+    };
+
+    let mut alerter = (!config.watches.is_empty()).then(|| {
+        alerts::Alerter::new(
+            &config.watches,
+            &config.alerts,
+            Duration::from_secs(config.alert_interval as u64),
+        )
+    });
+
+    let locator = config
+        .enrich_gs
+        .then(|| geo::StationLocator::new(&config.info.stations, config.receiver_location));
+
+    let evaluate_ticker = tick(Duration::from_secs(EVALUATE_INTERVAL_SECS));
+
+    let mut dedupe = config
+        .dedupe_window
+        .map(|ms| dedupe::Deduplicator::new(Duration::from_millis(ms)));
+    let flush_ticker = match dedupe {
+        Some(_) => tick(Duration::from_millis(DEDUPE_FLUSH_MS)),
+        None => never(),
+    };
+    let remote_digests = match (&cluster, &dedupe) {
+        (Some(cluster), Some(_)) => cluster.digests.clone(),
+        _ => never(),
+    };
+
+    // Only offer the chooser bands that at least one SDR is able to sample
+    let usable_bands: config::FrequencyBandMap = config
+        .info
+        .bands
+        .iter()
+        .filter(|(_, freqs)| {
+            config
+                .sdrs
+                .iter()
+                .any(|sdr| receiver::sdr_sample_rate(sdr, freqs).is_some())
+        })
+        .map(|(band, freqs)| (*band, freqs.clone()))
+        .collect();
+
+    let mut session: Option<session::Session> = None;
+    let mut calibration = calibration::Calibration::new();
+    let mut retune = false;
+
+    loop {
+        // Relaunch on the same bands when learned tuning calls for it, without
+        // giving the chooser a chance to move on
+        let allocation = match session.as_ref().filter(|_| retune) {
+            Some(current) => current.allocation(),
+            None => match choose_allocation(
+                plugin.as_mut(),
+                &props,
+                &usable_bands,
+                &gs_table,
+                &quarantine,
+                &cluster,
+                &config,
+            ) {
+                Some(allocation) => allocation,
+                None => return,
+            },
         };
+        retune = false;
 
-        let mut proc = match Command::new(config.bin.clone())
-            .stdout(Stdio::piped())
-            .stderr(Stdio::null())
-            .arg("--soapysdr")
-            .arg(config.driver.clone())
-            .arg("--system-table")
-            .arg(systable_temp_path.to_path_buf())
-            .arg("--sample-rate")
-            .arg(bandwidth)
-            .arg("--output")
-            .arg("decoded:json:file:path=-")
-            .args(&addition_args)
-            .args(band.into_iter().map(|f| f.to_string()))
-            .spawn()
-        {
-            Ok(proc) => proc,
-            Err(e) => {
-                error!("Failed to start dumphfdl: {}", e);
-                continue;
+        if let Some(current) = session.as_ref().filter(|c| c.allocation() == allocation) {
+            evaluate_session(current, &mut calibration, &gain_control, &quarantine, &config);
+        }
+
+        let tuned = tunings(&allocation, &calibration, &gain_control, &config);
+
+        // Keep dumphfdl running if the chooser elected to stay on the same bands,
+        // unless what was learned about them calls for retuning
+        session = match session.take() {
+            Some(current) if current.allocation() == allocation && !current.retuned(&tuned) => {
+                info!("Chooser kept the same bands, continuing session...");
+                Some(current)
+            }
+            Some(current) => {
+                end_session(
+                    current,
+                    &mut dedupe,
+                    &mut calibration,
+                    &gain_control,
+                    &quarantine,
+                    &mut router,
+                    &config,
+                );
+                None
             }
+            None => None,
         };
 
-        let (frame_send, frame_recv) = bounded(2048);
-        let child_stdout = match proc.stdout.take() {
-            Some(stdout) => stdout,
-            None => {
-                error!("Unable to get STDOUT for child dumphfdl process!");
-                continue;
-            }
+        let current = match session.as_mut() {
+            Some(current) => current,
+            None => match session::Session::start(&config, &systable_temp_path, &tuned) {
+                Some(current) => session.insert(current),
+                None => {
+                    thread::sleep(Duration::from_secs(1));
+                    continue;
+                }
+            },
         };
-        let reader_thread = thread::spawn(move || {
-            let mut reader = BufReader::new(child_stdout);
 
-            loop {
-                let mut line = String::new();
-                let size = match reader.read_line(&mut line) {
-                    Ok(size) => size,
+        let receivers = &current.receivers;
+        *receivers_status.lock().unwrap() = current.status();
+        if let Some(cluster) = &cluster {
+            let bands: Vec<u32> = receivers.iter().map(|r| r.status.band).collect();
+            cluster.set_bands(&bands);
+        }
+
+        // Every receiver waits out its own band's timeout, and the session only
+        // counts as inactive once all of them have gone quiet
+        let timeouts: Vec<(usize, u32, Duration)> = receivers
+            .iter()
+            .map(|r| {
+                let timeout = Duration::from_secs(config.timeout_for(r.status.band) as u64);
+                (r.status.id, r.status.band, timeout)
+            })
+            .collect();
+        for (sdr_idx, band, timeout) in timeouts.iter() {
+            info!(
+                "Receiver {} inactivity timeout on band {}: {}s",
+                sdr_idx,
+                band,
+                timeout.as_secs()
+            );
+        }
+
+        let mut last_activity: HashMap<usize, Instant> = timeouts
+            .iter()
+            .map(|(sdr_idx, _, _)| (*sdr_idx, Instant::now()))
+            .collect();
+        let mut exited = false;
+        let mut finished: Vec<usize> = vec![];
+        let mut died: Option<usize> = None;
+
+        loop {
+            // Bring back a receiver whose dumphfdl died while the others keep
+            // running, or start over if it cannot be
+            if let Some(sdr_idx) = died.take() {
+                error!("dumphfdl of receiver {} exited", sdr_idx);
+                match current.restart(sdr_idx, &config, &systable_temp_path) {
+                    Ok(()) => {
+                        *receivers_status.lock().unwrap() = current.status();
+                        last_activity.insert(sdr_idx, Instant::now());
+                    }
                     Err(e) => {
-                        error!("Reader thread encountered read error: {}", e);
+                        error!("Unable to restart receiver: {}", e);
+                        exited = true;
                         break;
                     }
-                };
-                if size == 0 {
-                    error!("Reader thread encountered empty read: exiting...");
-                    break;
-                }
-
-                if frame_send.send(line).is_err() {
-                    error!("Reader thread failed to send to main thread: exiting...");
-                    break;
                 }
             }
-        });
 
-        let timeout = Duration::from_secs(config.timeout as u64);
+            let timeout = timeouts
+                .iter()
+                .map(|(sdr_idx, band, timeout)| {
+                    plugin
+                        .timeout(*band, *timeout)
+                        .saturating_sub(last_activity[sdr_idx].elapsed())
+                })
+                .max()
+                .unwrap_or_default();
+            let wait = match plugin.deadline() {
+                Some(deadline) => timeout.min(deadline.saturating_duration_since(Instant::now())),
+                None => timeout,
+            };
 
-        loop {
             select! {
-                recv(frame_recv) -> msg => {
-                    if msg.is_ok() {
-                        let msg = msg.unwrap();
-
-                        let frame: Value = match serde_json::from_str(&msg) {
-                            Ok(val) => val,
-                            Err(e) => {
-                                error!("Bad JSON decode: {}", e);
+                recv(current.lines) -> line => {
+                    let (sdr_idx, msg) = match line {
+                        Ok((sdr_idx, Some(msg))) => (sdr_idx, msg),
+                        // Recordings end one receiver at a time, and the
+                        // session is over once all of them have
+                        Ok((sdr_idx, None)) if config.is_offline() => {
+                            finished.push(sdr_idx);
+                            if finished.len() < current.receivers.len() {
                                 continue;
-                            },
-                        };
+                            }
+                            exited = true;
+                            break;
+                        }
+                        Ok((sdr_idx, None)) => {
+                            died = Some(sdr_idx);
+                            continue;
+                        }
+                        Err(_) => {
+                            exited = true;
+                            break;
+                        }
+                    };
+                    last_activity.insert(sdr_idx, Instant::now());
 
-                        info!("Received {} byte frame...", msg.len());
-                        println!("{}", msg.trim());
+                    let mut frame: Value = match serde_json::from_str(&msg) {
+                        Ok(val) => val,
+                        Err(e) => {
+                            error!("Bad JSON decode: {}", e);
+                            continue;
+                        },
+                    };
 
-                        if plugin.on_update(&frame) {
-                            info!("Chooser update elected to change bands...");
-                            break;
+                    // Frames are passed through untouched unless viper adds to them
+                    let mut annotated = false;
+
+                    if let Ok(msg) = MessageFrame::from_value(&frame) {
+                        if let Some(report) = msg.acars().and_then(acars::decode) {
+                            frame::annotate(&mut frame, "acars", json!(report));
+                            annotated = true;
+                        }
+
+                        let band = current.band_of(sdr_idx).unwrap_or_default();
+                        if let Some(gs) = locator.as_ref().and_then(|locator| locator.describe(&msg)) {
+                            frame::annotate(&mut frame, "gs", gs);
+                            if let Some(band) = current.band_of(sdr_idx) {
+                                frame::annotate(&mut frame, "band", json!(band));
+                            }
+                            annotated = true;
+                        }
+                        gs_table.lock().unwrap().update(&msg, band);
+                        let mut aircraft_table = aircraft_table.lock().unwrap();
+                        let enrich = aircraft_table.has_reference();
+                        if let Some((state, positioned)) = aircraft_table.update(&msg, &frame) {
+                            if let (true, Some(log), Some(point)) = (positioned, track_log.as_mut(), tracks::TrackPoint::from_state(state)) {
+                                if let Err(e) = log.write(&point) {
+                                    error!("{}", e);
+                                }
+                            }
+                            if enrich {
+                                frame::annotate(&mut frame, "aircraft", json!({
+                                    "icao": state.icao,
+                                    "registration": state.registration,
+                                    "type": state.aircraft_type,
+                                    "operator": state.operator,
+                                }));
+                                annotated = true;
+                            }
+                        }
+                        drop(aircraft_table);
+                        if let Some(event) = load_table.lock().unwrap().update(&msg) {
+                            if let Some(Err(e)) = event_log.as_mut().map(|log| log.write(&event)) {
+                                error!("{}", e);
+                            }
+                            plugin.on_event(&event);
                         }
+                        if let Some(alerter) = alerter.as_mut() {
+                            alerter.check(&msg, &frame);
+                        }
+                        calibration.update(sdr_idx, &msg);
+                        if config.auto_gain {
+                            gain_control.lock().unwrap().update(band, &msg);
+                        }
+                    }
+                    if let (Some(quarantine), Some(band)) = (&quarantine, current.band_of(sdr_idx)) {
+                        quarantine.lock().unwrap().update(band, &frame);
+                    }
+
+                    info!("Received {} byte frame from receiver {}...", msg.len(), sdr_idx);
+                    match dedupe.as_mut() {
+                        Some(dedupe) => {
+                            let digest = dedupe.push(&config.receiver_name(sdr_idx), frame.clone());
+                            if let Some(cluster) = &cluster {
+                                cluster.publish(&digest);
+                            }
+                        }
+                        None => router.emit(frame.clone(), (!annotated).then_some(msg.as_str())),
+                    }
+
+                    if plugin.on_update(&frame) {
+                        info!("Chooser update elected to change bands...");
+                        break;
+                    }
+                },
+                recv(remote_digests) -> digest => {
+                    if let (Ok(digest), Some(dedupe)) = (digest, dedupe.as_mut()) {
+                        dedupe.merge(digest);
                     }
                 },
-                recv(after(timeout)) -> _ => {
+                recv(flush_ticker) -> _ => {
+                    if let Some(dedupe) = dedupe.as_mut() {
+                        for frame in dedupe.poll() {
+                            router.emit(frame, None);
+                        }
+                    }
+                },
+                recv(evaluate_ticker) -> _ => {
+                    evaluate_session(current, &mut calibration, &gain_control, &quarantine, &config);
+
+                    // Learned values only apply once dumphfdl is launched again,
+                    // which a chooser staying on its bands would never do
+                    let tuned = tunings(&current.allocation(), &calibration, &gain_control, &config);
+                    if !config.is_offline() && current.retuned(&tuned) {
+                        info!("Learned tuning changed, relaunching dumphfdl...");
+                        retune = true;
+                        break;
+                    }
+                },
+                recv(summary_ticker) -> _ => {
+                    gs_table.lock().unwrap().log_summary();
+                    if let Some(quarantine) = &quarantine {
+                        quarantine.lock().unwrap().log_summary();
+                    }
+                },
+                recv(after(wait)) -> _ => {
                     if plugin.on_timeout() {
                         info!("Timeout! Chooser elected to change bands...");
                         break;
                     }
+                    // The chooser stays, so wait a full timeout before asking again
+                    for seen in last_activity.values_mut() {
+                        *seen = Instant::now();
+                    }
                 },
             }
         }
 
-        proc.kill().unwrap();
-        reader_thread.join().unwrap();
+        if exited {
+            if let Some(current) = session.take() {
+                end_session(
+                    current,
+                    &mut dedupe,
+                    &mut calibration,
+                    &gain_control,
+                    &quarantine,
+                    &mut router,
+                    &config,
+                );
+            }
+            receivers_status.lock().unwrap().clear();
+
+            if config.is_offline() {
+                info!("IQ recording finished, exiting...");
+                return;
+            }
+            error!("dumphfdl exited, restarting session...");
+            thread::sleep(Duration::from_secs(1));
+        }
+    }
+}
+
+/// Asks the chooser for bands among those worth listening to and assigns
+/// them to SDRs. Returns `None` if nothing can be listened to.
+fn choose_allocation(
+    plugin: &mut dyn chooser::ChooserPlugin,
+    props: &HashMap<&str, &str>,
+    usable_bands: &config::FrequencyBandMap,
+    gs_table: &Mutex<gs_status::GroundStationTable>,
+    quarantine: &Option<Arc<Mutex<quarantine::Quarantine>>>,
+    cluster: &Option<cluster::Cluster>,
+    config: &config::Config,
+) -> Option<Vec<(usize, u32)>> {
+    let mut available = gs_table.lock().unwrap().filter(usable_bands);
+    if available.is_empty() {
+        available = usable_bands.clone();
+    }
+
+    let inactive = usable_bands.len() - available.len();
+    if inactive > 0 {
+        info!("Skipping {} band(s) with no active frequencies", inactive);
+    }
+
+    // Stay off jammed bands while enough others remain to keep every SDR busy
+    if let Some(quarantine) = quarantine {
+        let clear = quarantine.lock().unwrap().filter(&available);
+        if clear.len() < available.len() && clear.len() >= config.sdrs.len() {
+            info!(
+                "Skipping {} quarantined band(s)",
+                available.len() - clear.len()
+            );
+            available = clear;
+        }
+    }
+
+    // Leave bands watched by peers to them as long as there are enough
+    // other bands to keep every SDR busy
+    if let Some(cluster) = cluster {
+        let unwatched = cluster.view.lock().unwrap().filter(&available);
+        if unwatched.len() < available.len() && unwatched.len() >= config.sdrs.len() {
+            info!(
+                "Skipping {} band(s) watched by cluster peers",
+                available.len() - unwatched.len()
+            );
+            available = unwatched;
+        }
+    }
+
+    let skipped = usable_bands.len() - available.len();
+
+    let count = config.sdrs.len();
+    let chosen = match plugin.choose_set(&available, props, count).or_else(|e| {
+        if skipped == 0 {
+            return Err(e);
+        }
+
+        warn!(
+            "Chooser failed on available bands ({}), retrying with all bands",
+            e
+        );
+        plugin.choose_set(usable_bands, props, count)
+    }) {
+        Ok(val) => val,
+        Err(e) => {
+            error!("Failed to choose a frequency band to listen to: {}", e);
+            return None;
+        }
+    };
 
-        info!("Ending session...");
-        info!("");
+    let allocation = receiver::allocate(&config.sdrs, &chosen, &config.info.bands);
+    if allocation.is_empty() {
+        error!(
+            "Unable to allocate any of the chosen bands to an SDR: {:?}",
+            chosen
+        );
+        return None;
     }
+
+    Some(allocation)
+}
+
+/// Tuning every allocated receiver is launched with, including the frequency
+/// correction and gain learned so far
+fn tunings(
+    allocation: &[(usize, u32)],
+    calibration: &calibration::Calibration,
+    gain_control: &Mutex<gain::GainControl>,
+    config: &config::Config,
+) -> Vec<(usize, u32, config::Tuning)> {
+    allocation
+        .iter()
+        .map(|(sdr_idx, band)| {
+            let mut tuning = config.tuning_for(*band);
+            if let Some(ppm) = calibration.correction(*sdr_idx) {
+                tuning.freq_correction = Some(ppm);
+            }
+            if config.auto_gain {
+                if let Some(gain) = gain_control.lock().unwrap().gain_for(*band) {
+                    tuning.gain = Some(gain);
+                }
+            }
+            (*sdr_idx, *band, tuning)
+        })
+        .collect()
+}
+
+/// Estimates frequency correction and gain and checks for interference from
+/// the samples gathered since the last evaluation. Learned values take effect
+/// the next time dumphfdl is launched.
+fn evaluate_session(
+    session: &session::Session,
+    calibration: &mut calibration::Calibration,
+    gain_control: &Mutex<gain::GainControl>,
+    quarantine: &Option<Arc<Mutex<quarantine::Quarantine>>>,
+    config: &config::Config,
+) {
+    calibration.end_session(&session.freq_corrections(), config.auto_freq_correction);
+    if config.auto_gain {
+        gain_control.lock().unwrap().end_session(&session.gains());
+    }
+    if let Some(quarantine) = quarantine {
+        quarantine.lock().unwrap().end_session();
+    }
+}
+
+fn end_session(
+    session: session::Session,
+    dedupe: &mut Option<dedupe::Deduplicator>,
+    calibration: &mut calibration::Calibration,
+    gain_control: &Mutex<gain::GainControl>,
+    quarantine: &Option<Arc<Mutex<quarantine::Quarantine>>>,
+    router: &mut output::Router,
+    config: &config::Config,
+) {
+    evaluate_session(&session, calibration, gain_control, quarantine, config);
+    session.stop();
+
+    if let Some(dedupe) = dedupe.as_mut() {
+        for frame in dedupe.drain() {
+            router.emit(frame, None);
+        }
+    }
+
+    info!("Ending session...");
+    info!("");
 }
@@ -0,0 +1,237 @@
+use log::*;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::net::UdpSocket;
+use std::path::PathBuf;
+
+use crate::frame::{annotate, MessageFrame};
+use crate::http::{self, Poster};
+use crate::jsonlog::JsonLog;
+use crate::rules::{Action, Rule};
+
+pub const STDOUT_SINK: &str = "stdout";
+
+/// Named sinks in the order they were configured
+pub type SinkSpecs = Vec<(String, SinkSpec)>;
+
+/// Where frames routed to a sink are written
+#[derive(Debug, Clone)]
+pub enum SinkSpec {
+    Stdout,
+    File(PathBuf),
+    Udp(String),
+    Http(url::Url),
+}
+
+impl SinkSpec {
+    /// Parses `stdout`, `file:PATH`, `udp:HOST:PORT` or an `http(s)://` URL
+    pub fn parse(spec: &str) -> Result<SinkSpec, String> {
+        if spec == STDOUT_SINK {
+            return Ok(SinkSpec::Stdout);
+        }
+        if http::is_url(spec) {
+            return http::parse_url(spec).map(SinkSpec::Http);
+        }
+
+        match spec.split_once(':') {
+            Some(("file", path)) if !path.is_empty() => Ok(SinkSpec::File(PathBuf::from(path))),
+            Some(("udp", addr)) if addr.contains(':') => Ok(SinkSpec::Udp(addr.to_string())),
+            _ => Err(format!(
+                "Sink is not stdout, file:PATH, udp:HOST:PORT or http(s)://URL: {}",
+                spec
+            )),
+        }
+    }
+}
+
+enum Sink {
+    Stdout,
+    File(JsonLog),
+    Udp(UdpSocket, String),
+    Http(Poster),
+}
+
+impl Sink {
+    fn open(spec: &SinkSpec) -> Result<Sink, String> {
+        match spec {
+            SinkSpec::Stdout => Ok(Sink::Stdout),
+            SinkSpec::File(path) => JsonLog::open(path).map(Sink::File),
+            SinkSpec::Udp(addr) => UdpSocket::bind("0.0.0.0:0")
+                .map(|socket| Sink::Udp(socket, addr.clone()))
+                .map_err(|e| format!("Unable to open UDP sink {}: {}", addr, e)),
+            SinkSpec::Http(url) => Ok(Sink::Http(Poster::start(url.clone()))),
+        }
+    }
+
+    /// `raw` is the frame exactly as dumphfdl printed it, if viper left it
+    /// untouched
+    fn write(&mut self, frame: &Value, raw: Option<&str>) -> Result<(), String> {
+        match self {
+            Sink::Stdout => {
+                match raw {
+                    Some(raw) => println!("{}", raw.trim()),
+                    None => println!("{}", frame),
+                }
+                Ok(())
+            }
+            Sink::File(log) => log.write(frame),
+            Sink::Udp(socket, addr) => socket
+                .send_to(frame.to_string().as_bytes(), addr.as_str())
+                .map(|_| ())
+                .map_err(|e| format!("Unable to send frame to {}: {}", addr, e)),
+            Sink::Http(poster) => {
+                poster.send(frame.to_string());
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Applies the configured rules to every output frame and writes it to the
+/// sink it is routed to, stdout unless a rule says otherwise
+pub struct Router {
+    rules: Vec<Rule>,
+    sinks: HashMap<String, Sink>,
+}
+
+impl Router {
+    pub fn new(rules: &[Rule], sinks: &SinkSpecs) -> Result<Router, String> {
+        let mut opened: HashMap<String, Sink> = HashMap::new();
+        opened.insert(STDOUT_SINK.to_string(), Sink::Stdout);
+        for (name, spec) in sinks.iter() {
+            opened.insert(name.clone(), Sink::open(spec)?);
+        }
+
+        Ok(Router {
+            rules: rules.to_vec(),
+            sinks: opened,
+        })
+    }
+
+    /// Sink a frame goes to and the tags it collected on the way, or `None`
+    /// if it is dropped. The first drop or route rule that matches wins.
+    fn route(&self, frame: &Value) -> Option<(&str, Vec<&str>)> {
+        let msg = match self.rules.is_empty() {
+            true => None,
+            false => MessageFrame::from_value(frame).ok(),
+        };
+
+        let mut tags: Vec<&str> = vec![];
+
+        for rule in self.rules.iter() {
+            if !rule.matches(msg.as_ref(), frame) {
+                continue;
+            }
+
+            match &rule.action {
+                Action::Drop => return None,
+                Action::Route(name) => return Some((name, tags)),
+                Action::Tag(tag) => tags.push(tag),
+            }
+        }
+
+        Some((STDOUT_SINK, tags))
+    }
+
+    pub fn emit(&mut self, mut frame: Value, mut raw: Option<&str>) {
+        let (sink, tags) = match self.route(&frame) {
+            Some((sink, tags)) => (sink.to_string(), tags),
+            None => return,
+        };
+
+        if !tags.is_empty() {
+            annotate(&mut frame, "tags", json!(tags));
+            raw = None;
+        }
+
+        let result = match self.sinks.get_mut(&sink) {
+            Some(sink) => sink.write(&frame, raw),
+            None => Err(format!("Unknown sink: {}", sink)),
+        };
+        if let Err(e) = result {
+            error!("{}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn router(rules: &[&str]) -> Router {
+        let rules: Vec<Rule> = rules.iter().map(|r| Rule::parse(r).unwrap()).collect();
+        Router::new(&rules, &vec![]).unwrap()
+    }
+
+    fn spdu() -> Value {
+        json!({"hfdl": {"freq": 13276000, "spdu": {
+            "err": false,
+            "src": {"type": "Ground station", "id": 1, "name": "San Francisco, California"},
+            "gs_status": []
+        }}})
+    }
+
+    #[test]
+    fn sink_spec_parse() {
+        assert!(matches!(SinkSpec::parse("stdout"), Ok(SinkSpec::Stdout)));
+        assert!(matches!(
+            SinkSpec::parse("file:out.jsonl"),
+            Ok(SinkSpec::File(_))
+        ));
+        assert!(matches!(
+            SinkSpec::parse("udp:127.0.0.1:5555"),
+            Ok(SinkSpec::Udp(_))
+        ));
+        assert!(matches!(
+            SinkSpec::parse("http://localhost:9200/hfdl/_doc"),
+            Ok(SinkSpec::Http(_))
+        ));
+
+        assert!(matches!(
+            SinkSpec::parse("https://es.example.com:9200/hfdl/_doc"),
+            Ok(SinkSpec::Http(_))
+        ));
+
+        assert!(SinkSpec::parse("file:").is_err());
+        assert!(SinkSpec::parse("udp:localhost").is_err());
+        assert!(SinkSpec::parse("tcp:localhost:1").is_err());
+    }
+
+    #[test]
+    fn unmatched_frames_go_to_stdout() {
+        assert_eq!(router(&[]).route(&spdu()), Some((STDOUT_SINK, vec![])));
+        assert_eq!(
+            router(&["type=lpdu -> route:other"]).route(&spdu()),
+            Some((STDOUT_SINK, vec![]))
+        );
+    }
+
+    #[test]
+    fn first_route_wins_and_tags_accumulate() {
+        let router = router(&[
+            "type=spdu -> tag:squitter",
+            "type=lpdu -> tag:link",
+            "gs=San -> tag:sfo",
+            "type=spdu -> route:first",
+            "* -> tag:late",
+            "* -> route:second",
+        ]);
+
+        assert_eq!(
+            router.route(&spdu()),
+            Some(("first", vec!["squitter", "sfo"]))
+        );
+    }
+
+    #[test]
+    fn drop_stops_routing() {
+        assert_eq!(
+            router(&["* -> tag:seen", "type=spdu -> drop"]).route(&spdu()),
+            None
+        );
+        assert_eq!(
+            router(&["type=spdu -> route:first", "* -> drop"]).route(&spdu()),
+            Some(("first", vec![]))
+        );
+    }
+}
@@ -0,0 +1,289 @@
+use log::*;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, Instant};
+
+use crate::config::FrequencyBandMap;
+use crate::frame::has_error;
+use crate::util::unix_now;
+
+const MIN_FRAMES: usize = 10;
+
+/// Weight given to a new session's noise floor when updating a band's baseline
+const BASELINE_WEIGHT: f64 = 0.25;
+
+#[derive(Default)]
+struct Stats {
+    frames: usize,
+    errors: usize,
+    noise: Vec<f64>,
+}
+
+struct Quarantined {
+    since: u64,
+    until: Instant,
+    reason: String,
+}
+
+/// Temporarily removes bands whose decode error rate or noise floor points to
+/// local interference
+pub struct Quarantine {
+    cooldown: Duration,
+    max_error_rate: f64,
+    noise_margin: f64,
+
+    baselines: HashMap<u32, f64>,
+    stats: HashMap<u32, Stats>,
+    quarantined: BTreeMap<u32, Quarantined>,
+}
+
+impl Quarantine {
+    pub fn new(cooldown: Duration, max_error_rate: f64, noise_margin: f64) -> Self {
+        Quarantine {
+            cooldown,
+            max_error_rate,
+            noise_margin,
+            baselines: HashMap::new(),
+            stats: HashMap::new(),
+            quarantined: BTreeMap::new(),
+        }
+    }
+
+    pub fn update(&mut self, band: u32, frame: &Value) {
+        let stats = self.stats.entry(band).or_default();
+        stats.frames += 1;
+        if has_error(frame) {
+            stats.errors += 1;
+        }
+        if let Some(noise) = frame.pointer("/hfdl/noise_level").and_then(Value::as_f64) {
+            stats.noise.push(noise);
+        }
+    }
+
+    /// Median baseline of the bands other than `band`. A band first heard
+    /// while jammed would otherwise learn the interference as its baseline.
+    fn reference(&self, band: u32) -> Option<f64> {
+        let mut baselines: Vec<f64> = self
+            .baselines
+            .iter()
+            .filter(|(other, _)| **other != band)
+            .map(|(_, baseline)| *baseline)
+            .collect();
+        if baselines.is_empty() {
+            return None;
+        }
+
+        baselines.sort_by(|a, b| a.total_cmp(b));
+        Some(baselines[baselines.len() / 2])
+    }
+
+    /// Checks every band heard this session, quarantining the ones that look
+    /// jammed and folding the noise floor of the rest into their baseline
+    pub fn end_session(&mut self) {
+        let sessions: Vec<(u32, Stats)> = self.stats.drain().collect();
+        for (band, mut stats) in sessions {
+            if stats.frames < MIN_FRAMES {
+                continue;
+            }
+
+            let error_rate = stats.errors as f64 / stats.frames as f64;
+            let noise = match stats.noise.is_empty() {
+                true => None,
+                false => {
+                    stats.noise.sort_by(|a, b| a.total_cmp(b));
+                    Some(stats.noise[stats.noise.len() / 2])
+                }
+            };
+
+            let reason = if error_rate > self.max_error_rate {
+                Some(format!(
+                    "{}/{} frames with errors",
+                    stats.errors, stats.frames
+                ))
+            } else {
+                let baseline = match self.baselines.get(&band) {
+                    Some(baseline) => Some((*baseline, "baseline")),
+                    None => self.reference(band).map(|r| (r, "baseline of other bands")),
+                };
+                match (noise, baseline) {
+                    (Some(noise), Some((baseline, of))) if noise > baseline + self.noise_margin => {
+                        Some(format!(
+                            "noise floor {:.1} dBFS vs {:.1} dBFS {}",
+                            noise, baseline, of
+                        ))
+                    }
+                    _ => None,
+                }
+            };
+
+            match reason {
+                Some(reason) => {
+                    warn!(
+                        "Quarantining band {} for {}s: {}",
+                        band,
+                        self.cooldown.as_secs(),
+                        reason
+                    );
+                    self.quarantined.insert(
+                        band,
+                        Quarantined {
+                            since: unix_now(),
+                            until: Instant::now() + self.cooldown,
+                            reason,
+                        },
+                    );
+                }
+                None => {
+                    if let Some(noise) = noise {
+                        let baseline = self.baselines.entry(band).or_insert(noise);
+                        *baseline += (noise - *baseline) * BASELINE_WEIGHT;
+                    }
+                }
+            }
+        }
+    }
+
+    fn expire(&mut self) {
+        let now = Instant::now();
+        self.quarantined.retain(|band, q| {
+            if q.until > now {
+                return true;
+            }
+
+            info!("Band {} released from quarantine", band);
+            false
+        });
+    }
+
+    /// Returns the bands that are not currently quarantined
+    pub fn filter(&mut self, bands: &FrequencyBandMap) -> FrequencyBandMap {
+        self.expire();
+
+        bands
+            .iter()
+            .filter(|(band, _)| !self.quarantined.contains_key(band))
+            .map(|(band, freqs)| (*band, freqs.clone()))
+            .collect()
+    }
+
+    pub fn log_summary(&mut self) {
+        self.expire();
+        if self.quarantined.is_empty() {
+            return;
+        }
+
+        info!("Quarantined bands:");
+        for (band, q) in self.quarantined.iter() {
+            info!(
+                "  {:>5} {}s left: {}",
+                band,
+                q.until.saturating_duration_since(Instant::now()).as_secs(),
+                q.reason
+            );
+        }
+    }
+
+    pub fn snapshot(&self) -> Value {
+        let now = Instant::now();
+        let bands: Vec<Value> = self
+            .quarantined
+            .iter()
+            .filter(|(_, q)| q.until > now)
+            .map(|(band, q)| {
+                json!({
+                    "band": band,
+                    "since": q.since,
+                    "remaining": q.until.saturating_duration_since(now).as_secs(),
+                    "reason": q.reason,
+                })
+            })
+            .collect();
+
+        Value::Array(bands)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    fn quarantine(cooldown: Duration) -> Quarantine {
+        Quarantine::new(cooldown, 0.5, 10.0)
+    }
+
+    /// Feeds a session of `band` with `errors` failed frames out of
+    /// MIN_FRAMES, all at the given noise floor
+    fn session(quarantine: &mut Quarantine, band: u32, errors: usize, noise: f64) {
+        for i in 0..MIN_FRAMES {
+            let frame = json!({"hfdl": {
+                "noise_level": noise,
+                "lpdu": {"err": i < errors}
+            }});
+            quarantine.update(band, &frame);
+        }
+        quarantine.end_session();
+    }
+
+    fn bands() -> FrequencyBandMap {
+        FrequencyBandMap::from([(8, vec![8927]), (13, vec![13276]), (21, vec![21934])])
+    }
+
+    fn clear_bands(quarantine: &mut Quarantine) -> Vec<u32> {
+        let mut bands: Vec<u32> = quarantine.filter(&bands()).into_keys().collect();
+        bands.sort_unstable();
+        bands
+    }
+
+    #[test]
+    fn quarantines_on_error_rate_until_cooldown_ends() {
+        let mut quarantine = quarantine(Duration::from_millis(50));
+        session(&mut quarantine, 13, 5, -50.0);
+        assert_eq!(clear_bands(&mut quarantine), vec![8, 13, 21]);
+
+        session(&mut quarantine, 13, 6, -50.0);
+        assert_eq!(clear_bands(&mut quarantine), vec![8, 21]);
+        assert_eq!(quarantine.snapshot()[0]["band"], json!(13));
+
+        thread::sleep(Duration::from_millis(60));
+        assert_eq!(clear_bands(&mut quarantine), vec![8, 13, 21]);
+        assert_eq!(quarantine.snapshot(), json!([]));
+    }
+
+    #[test]
+    fn quarantines_noise_above_the_band_baseline() {
+        let mut quarantine = quarantine(Duration::from_secs(60));
+        session(&mut quarantine, 8, 0, -40.0);
+        session(&mut quarantine, 8, 0, -31.0);
+        assert_eq!(clear_bands(&mut quarantine), vec![8, 13, 21]);
+
+        // The baseline moved a quarter of the way towards -31 dBFS
+        session(&mut quarantine, 8, 0, -29.0);
+        assert_eq!(clear_bands(&mut quarantine), vec![8, 13, 21]);
+        session(&mut quarantine, 8, 0, -20.0);
+        assert_eq!(clear_bands(&mut quarantine), vec![13, 21]);
+    }
+
+    #[test]
+    fn band_jammed_from_the_start_is_held_to_other_bands() {
+        let mut quarantine = quarantine(Duration::from_secs(60));
+        session(&mut quarantine, 8, 0, -50.0);
+        session(&mut quarantine, 13, 0, -52.0);
+
+        session(&mut quarantine, 21, 0, -30.0);
+        assert_eq!(clear_bands(&mut quarantine), vec![8, 13]);
+        assert!(!quarantine.baselines.contains_key(&21));
+
+        // Once clean it learns a baseline of its own
+        session(&mut quarantine, 21, 0, -55.0);
+        assert_eq!(quarantine.baselines.get(&21), Some(&-55.0));
+    }
+
+    #[test]
+    fn short_sessions_are_not_judged() {
+        let mut quarantine = quarantine(Duration::from_secs(60));
+        quarantine.update(13, &json!({"hfdl": {"lpdu": {"err": true}}}));
+        quarantine.end_session();
+        assert_eq!(clear_bands(&mut quarantine), vec![8, 13, 21]);
+    }
+}
@@ -0,0 +1,91 @@
+use log::*;
+use serde_json::{json, Map, Value};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::aircraft::AircraftTable;
+
+/// Seconds elapsed since `ts`, rounded to a tenth like readsb does
+fn seen(now: f64, ts: u64) -> f64 {
+    ((now - ts as f64) * 10.0).round().max(0.0) / 10.0
+}
+
+/// Renders the aircraft table as a readsb/tar1090 `aircraft.json` document
+pub fn aircraft_json(table: &AircraftTable) -> Value {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0.0, |d| d.as_secs_f64());
+
+    let mut messages = 0;
+    let aircraft: Vec<Value> = table
+        .active()
+        .map(|state| {
+            messages += state.frames;
+
+            let mut entry = Map::new();
+            entry.insert("hex".to_string(), json!(state.icao.to_lowercase()));
+            entry.insert("type".to_string(), json!("other"));
+            if let Some(flight) = &state.flight_id {
+                entry.insert("flight".to_string(), json!(flight));
+            }
+            if let Some(reg) = &state.registration {
+                entry.insert("r".to_string(), json!(reg));
+            }
+            if let Some(aircraft_type) = &state.aircraft_type {
+                entry.insert("t".to_string(), json!(aircraft_type));
+            }
+            if let Some(operator) = &state.operator {
+                entry.insert("ownOp".to_string(), json!(operator));
+            }
+            if let (Some(lat), Some(lon)) = (state.lat, state.lon) {
+                entry.insert("lat".to_string(), json!(lat));
+                entry.insert("lon".to_string(), json!(lon));
+            }
+            if let Some(altitude) = state.altitude {
+                entry.insert("alt_baro".to_string(), json!(altitude));
+            }
+            if let Some(updated) = state.position_updated {
+                entry.insert("seen_pos".to_string(), json!(seen(now, updated)));
+            }
+            entry.insert("seen".to_string(), json!(seen(now, state.last_seen)));
+            entry.insert("messages".to_string(), json!(state.frames));
+
+            Value::Object(entry)
+        })
+        .collect();
+
+    json!({
+        "now": now,
+        "messages": messages,
+        "aircraft": aircraft,
+    })
+}
+
+fn write(path: &PathBuf, table: &Mutex<AircraftTable>) -> Result<(), String> {
+    let contents = aircraft_json(&table.lock().unwrap()).to_string();
+
+    // Write next to the target and rename so readers never see a partial file
+    let mut temp = path.clone().into_os_string();
+    temp.push(".tmp");
+    fs::write(&temp, contents).map_err(|e| format!("Unable to write {:?}: {}", temp, e))?;
+    fs::rename(&temp, path).map_err(|e| format!("Unable to replace {:?}: {}", path, e))
+}
+
+/// Periodically rewrites `path` with the current aircraft table
+pub fn start_writer(path: PathBuf, interval: Duration, table: Arc<Mutex<AircraftTable>>) {
+    info!(
+        "Writing aircraft.json to {:?} every {}s",
+        path,
+        interval.as_secs()
+    );
+
+    thread::spawn(move || loop {
+        if let Err(e) = write(&path, &table) {
+            error!("{}", e);
+        }
+        thread::sleep(interval);
+    });
+}
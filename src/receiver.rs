@@ -0,0 +1,336 @@
+use crossbeam::channel::Sender;
+use log::*;
+use serde::Serialize;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::thread::{self, JoinHandle};
+use std::time::Instant;
+
+use crate::config::{Config, FrequencyBandMap, Sdr, Source, Tuning};
+
+/// Line of dumphfdl output tagged with the index of the receiver it came from,
+/// followed by `None` once that receiver's dumphfdl has exited
+pub type ReceiverLine = (usize, Option<String>);
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ReceiverStatus {
+    pub id: usize,
+    pub source: String,
+    pub band: u32,
+    pub freqs: Vec<u32>,
+    pub sample_rate: u32,
+    pub gain: Option<f64>,
+    pub antenna: Option<String>,
+    pub freq_correction: Option<f64>,
+}
+
+pub struct Receiver {
+    pub status: ReceiverStatus,
+    pub tuning: Tuning,
+    pub started: Instant,
+
+    proc: Child,
+    reader: JoinHandle<()>,
+}
+
+/// Smallest sample rate able to cover every frequency in the band
+pub fn sample_rate(freqs: &[u32]) -> Option<u32> {
+    match freqs.iter().max().unwrap_or(&0) - freqs.iter().min().unwrap_or(&0) {
+        d if d > 256 && d <= 384 => Some(384000),
+        d if d <= 256 => Some(256000),
+        _ => None,
+    }
+}
+
+/// Sample rate `sdr` needs to cover every frequency in the band, if it can
+pub fn sdr_sample_rate(sdr: &Sdr, freqs: &[u32]) -> Option<u32> {
+    match &sdr.source {
+        Source::Soapy { .. } => sample_rate(freqs).filter(|rate| *rate <= sdr.max_sample_rate),
+        Source::IqFile { centerfreq, .. } => {
+            let half_span = sdr.max_sample_rate / 2000;
+            let lowest = centerfreq.saturating_sub(half_span);
+            let highest = centerfreq + half_span;
+
+            freqs
+                .iter()
+                .all(|f| *f >= lowest && *f <= highest)
+                .then_some(sdr.max_sample_rate)
+        }
+    }
+}
+
+/// dumphfdl SoapySDR arguments for the tuning options that are set
+fn tuning_args(tuning: &Tuning, freqs: &[u32], sample_rate: u32) -> Vec<String> {
+    let mut args: Vec<String> = vec![];
+
+    if let Some(gain) = tuning.gain {
+        args.extend(["--gain".to_string(), gain.to_string()]);
+    }
+    if let Some(elements) = &tuning.gain_elements {
+        args.extend(["--gain-elements".to_string(), elements.clone()]);
+    }
+    if let Some(antenna) = &tuning.antenna {
+        args.extend(["--antenna".to_string(), antenna.clone()]);
+    }
+    if let Some(ppm) = tuning.freq_correction {
+        args.extend(["--freq-correction".to_string(), ppm.to_string()]);
+    }
+    if let Some(settings) = &tuning.device_settings {
+        args.extend(["--device-settings".to_string(), settings.clone()]);
+    }
+
+    if let Some(offset) = tuning.centerfreq_offset {
+        let lowest = *freqs.iter().min().unwrap_or(&0) as i64;
+        let highest = *freqs.iter().max().unwrap_or(&0) as i64;
+        let center = (lowest + highest) / 2 + offset as i64;
+        let half_span = (sample_rate / 2000) as i64;
+
+        if center - half_span <= lowest && highest <= center + half_span {
+            args.extend(["--centerfreq".to_string(), center.to_string()]);
+        } else {
+            warn!(
+                "Center frequency offset {}kHz pushes {:?} out of the sampled range, ignoring",
+                offset, freqs
+            );
+        }
+    }
+
+    args
+}
+
+/// Assigns every chosen band to a distinct SDR able to sample it, preferring
+/// to give the widest bands to the most capable SDRs. Returns pairs of
+/// (SDR index, band).
+pub fn allocate(sdrs: &[Sdr], chosen: &[u32], bands: &FrequencyBandMap) -> Vec<(usize, u32)> {
+    let mut wanted: Vec<(u32, u32)> = chosen
+        .iter()
+        .filter_map(|band| {
+            let rate = bands.get(band).and_then(|freqs| sample_rate(freqs));
+            rate.map(|rate| (*band, rate))
+        })
+        .collect();
+    wanted.sort_by_key(|(_, rate)| std::cmp::Reverse(*rate));
+
+    let mut free: Vec<usize> = (0..sdrs.len()).collect();
+    free.sort_by_key(|idx| std::cmp::Reverse(sdrs[*idx].max_sample_rate));
+
+    let mut allocation: Vec<(usize, u32)> = vec![];
+    for (band, rate) in wanted.into_iter() {
+        let freqs = &bands[&band];
+
+        // Take the least capable SDR that can still cover the band
+        match free
+            .iter()
+            .rposition(|idx| sdr_sample_rate(&sdrs[*idx], freqs).is_some())
+        {
+            Some(pos) => allocation.push((free.remove(pos), band)),
+            None => warn!("No free SDR can sample band {} at {}Hz", band, rate),
+        }
+    }
+
+    allocation.sort_unstable();
+    allocation
+}
+
+impl Receiver {
+    pub fn spawn(
+        id: usize,
+        config: &Config,
+        systable: &Path,
+        band: u32,
+        tuning: Tuning,
+        lines: Sender<ReceiverLine>,
+    ) -> Result<Receiver, String> {
+        let sdr = &config.sdrs[id];
+        let freqs = match config.info.bands.get(&band) {
+            Some(freqs) => freqs.clone(),
+            None => return Err(format!("Unknown band: {}", band)),
+        };
+        let sample_rate = match sdr_sample_rate(sdr, &freqs) {
+            Some(rate) => rate,
+            None => return Err(format!("Bandwidth calculation failed: {:?}", freqs)),
+        };
+
+        let mut command = Command::new(&config.bin);
+        command.stdout(Stdio::piped()).stderr(Stdio::null());
+        match &sdr.source {
+            Source::Soapy { driver } => {
+                command.arg("--soapysdr").arg(driver).args(tuning_args(
+                    &tuning,
+                    &freqs,
+                    sample_rate,
+                ));
+            }
+            Source::IqFile {
+                path,
+                format,
+                centerfreq,
+            } => {
+                command
+                    .arg("--iq-file")
+                    .arg(path)
+                    .arg("--sample-format")
+                    .arg(format.to_uppercase())
+                    .arg("--centerfreq")
+                    .arg(centerfreq.to_string());
+            }
+        }
+        command
+            .arg("--system-table")
+            .arg(systable)
+            .arg("--sample-rate")
+            .arg(sample_rate.to_string())
+            .arg("--output")
+            .arg("decoded:json:file:path=-");
+        if let Some(output) = &config.output {
+            command.arg("--output").arg(output);
+        }
+        command.args(freqs.iter().map(|f| f.to_string()));
+
+        let mut proc = command
+            .spawn()
+            .map_err(|e| format!("Failed to start dumphfdl: {}", e))?;
+
+        let child_stdout = match proc.stdout.take() {
+            Some(stdout) => stdout,
+            None => {
+                let _ = proc.kill();
+                return Err("Unable to get STDOUT for child dumphfdl process!".to_string());
+            }
+        };
+
+        let reader = thread::spawn(move || {
+            let mut reader = BufReader::new(child_stdout);
+
+            loop {
+                let mut line = String::new();
+                let size = match reader.read_line(&mut line) {
+                    Ok(size) => size,
+                    Err(e) => {
+                        error!("Reader thread {} encountered read error: {}", id, e);
+                        break;
+                    }
+                };
+                if size == 0 {
+                    error!("Reader thread {} encountered empty read: exiting...", id);
+                    break;
+                }
+
+                if lines.send((id, Some(line))).is_err() {
+                    error!(
+                        "Reader thread {} failed to send to main thread: exiting...",
+                        id
+                    );
+                    break;
+                }
+            }
+            let _ = lines.send((id, None));
+        });
+
+        Ok(Receiver {
+            status: ReceiverStatus {
+                id,
+                source: sdr.to_string(),
+                band,
+                freqs,
+                sample_rate,
+                gain: tuning.gain,
+                antenna: tuning.antenna.clone(),
+                freq_correction: tuning.freq_correction,
+            },
+            tuning,
+            started: Instant::now(),
+            proc,
+            reader,
+        })
+    }
+
+    pub fn stop(mut self) {
+        if let Err(e) = self.proc.kill() {
+            warn!(
+                "Failed to kill dumphfdl for receiver {}: {}",
+                self.status.id, e
+            );
+        }
+        let _ = self.proc.wait();
+        let _ = self.reader.join();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn soapy(max_sample_rate: u32) -> Sdr {
+        Sdr {
+            source: Source::Soapy {
+                driver: "driver=airspyhf".to_string(),
+            },
+            max_sample_rate,
+        }
+    }
+
+    fn bands() -> FrequencyBandMap {
+        FrequencyBandMap::from([
+            (5, vec![5451, 5508, 5720]),
+            (8, vec![8912, 8927, 8977]),
+            (13, vec![13276, 13312]),
+            (21, vec![21934]),
+            (99, vec![2941, 3455]),
+        ])
+    }
+
+    #[test]
+    fn sample_rate_covers_band_span() {
+        assert_eq!(sample_rate(&[]), Some(256000));
+        assert_eq!(sample_rate(&[21934]), Some(256000));
+        assert_eq!(sample_rate(&[13000, 13256]), Some(256000));
+        assert_eq!(sample_rate(&[13000, 13257]), Some(384000));
+        assert_eq!(sample_rate(&[13000, 13200, 13384]), Some(384000));
+        assert_eq!(sample_rate(&[13000, 13385]), None);
+    }
+
+    #[test]
+    fn sdr_sample_rate_respects_source_limits() {
+        let wide = [5451, 5720];
+        assert_eq!(sdr_sample_rate(&soapy(384000), &wide), Some(384000));
+        assert_eq!(sdr_sample_rate(&soapy(256000), &wide), None);
+
+        let recording = Sdr {
+            source: Source::IqFile {
+                path: PathBuf::from("recording.cs16"),
+                format: "cs16".to_string(),
+                centerfreq: 13300,
+            },
+            max_sample_rate: 256000,
+        };
+        assert_eq!(sdr_sample_rate(&recording, &[13276, 13312]), Some(256000));
+        assert_eq!(sdr_sample_rate(&recording, &[13172, 13428]), Some(256000));
+        assert_eq!(sdr_sample_rate(&recording, &[13171]), None);
+        assert_eq!(sdr_sample_rate(&recording, &[21934]), None);
+    }
+
+    #[test]
+    fn allocate_gives_widest_bands_to_most_capable_sdrs() {
+        let sdrs = [soapy(256000), soapy(384000)];
+
+        assert_eq!(allocate(&sdrs, &[13, 5], &bands()), vec![(0, 13), (1, 5)]);
+        assert_eq!(allocate(&sdrs, &[21, 13], &bands()), vec![(0, 21), (1, 13)]);
+    }
+
+    #[test]
+    fn allocate_skips_bands_no_free_sdr_can_sample() {
+        let sdrs = [soapy(256000), soapy(256000)];
+        assert_eq!(
+            allocate(&sdrs, &[5, 8, 13], &bands()),
+            vec![(0, 13), (1, 8)]
+        );
+
+        // More bands than SDRs, unknown bands and bands too wide for any SDR
+        let sdrs = [soapy(384000)];
+        assert_eq!(allocate(&sdrs, &[99, 7, 21, 13], &bands()), vec![(0, 21)]);
+        assert!(allocate(&sdrs, &[], &bands()).is_empty());
+    }
+}
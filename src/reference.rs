@@ -0,0 +1,302 @@
+use log::*;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+/// How often reference files are checked for changes
+const RELOAD_CHECK_SECS: u64 = 10;
+
+#[derive(Clone, Debug, Default)]
+pub struct AircraftInfo {
+    pub registration: Option<String>,
+    pub aircraft_type: Option<String>,
+}
+
+/// Splits a CSV line into trimmed fields. Quoted fields may contain commas
+/// and `""` escaped quotes. Blank lines and `#` comments yield no fields.
+fn csv_fields(line: &str) -> Vec<String> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return vec![];
+    }
+
+    let mut fields = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field).trim().to_string()),
+            _ => field.push(c),
+        }
+    }
+    fields.push(field.trim().to_string());
+
+    fields
+}
+
+fn non_empty(field: Option<&String>) -> Option<String> {
+    field.filter(|f| !f.is_empty()).cloned()
+}
+
+/// Parses `ICAO,REGISTRATION,TYPE` lines keyed by upper case ICAO address,
+/// skipping malformed lines
+fn parse_aircraft(contents: &str) -> Result<HashMap<String, AircraftInfo>, String> {
+    let mut aircraft = HashMap::new();
+
+    for (idx, line) in contents.lines().enumerate() {
+        let fields = csv_fields(line);
+        let icao = match fields.first() {
+            Some(icao) if icao.len() == 6 && icao.chars().all(|c| c.is_ascii_hexdigit()) => {
+                icao.to_uppercase()
+            }
+            Some(icao) if idx == 0 && icao.eq_ignore_ascii_case("icao") => continue,
+            Some(icao) => {
+                warn!(
+                    "Skipping aircraft line {}: invalid ICAO address '{}'",
+                    idx + 1,
+                    icao
+                );
+                continue;
+            }
+            None => continue,
+        };
+
+        aircraft.insert(
+            icao,
+            AircraftInfo {
+                registration: non_empty(fields.get(1)),
+                aircraft_type: non_empty(fields.get(2)),
+            },
+        );
+    }
+
+    Ok(aircraft)
+}
+
+/// Parses `CODE,NAME` lines keyed by upper case airline designator, skipping
+/// malformed lines
+fn parse_airlines(contents: &str) -> Result<HashMap<String, String>, String> {
+    let mut airlines = HashMap::new();
+
+    for (idx, line) in contents.lines().enumerate() {
+        let fields = csv_fields(line);
+        match (fields.first(), non_empty(fields.get(1))) {
+            (None, _) => continue,
+            (Some(code), _) if idx == 0 && code.eq_ignore_ascii_case("code") => continue,
+            (Some(code), Some(name))
+                if (2..=3).contains(&code.len())
+                    && code.chars().all(|c| c.is_ascii_alphanumeric()) =>
+            {
+                airlines.insert(code.to_uppercase(), name);
+            }
+            (Some(code), _) => {
+                warn!(
+                    "Skipping airline line {}: invalid entry '{}'",
+                    idx + 1,
+                    code
+                );
+            }
+        }
+    }
+
+    Ok(airlines)
+}
+
+/// A reference file and the modification time it was last loaded at
+struct Source<T> {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    data: T,
+}
+
+impl<T> Source<T> {
+    fn modified(path: &Path) -> Option<SystemTime> {
+        fs::metadata(path).and_then(|meta| meta.modified()).ok()
+    }
+
+    fn load(path: &Path, parse: fn(&str) -> Result<T, String>) -> Result<Source<T>, String> {
+        let modified = Self::modified(path);
+        let data = fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|contents| parse(&contents))
+            .map_err(|e| format!("Unable to load {:?}: {}", path, e))?;
+
+        Ok(Source {
+            path: path.to_path_buf(),
+            modified,
+            data,
+        })
+    }
+
+    /// Reloads the file if it was modified since it was last loaded, keeping
+    /// the previous data if it no longer parses
+    fn refresh(&mut self, parse: fn(&str) -> Result<T, String>) -> bool {
+        let modified = Self::modified(&self.path);
+        if modified.is_none() || modified == self.modified {
+            return false;
+        }
+
+        match Source::load(&self.path, parse) {
+            Ok(source) => {
+                *self = source;
+                true
+            }
+            Err(e) => {
+                warn!("{}, keeping previous contents", e);
+                self.modified = modified;
+                false
+            }
+        }
+    }
+}
+
+/// Local aircraft and airline reference data, reloaded whenever the files
+/// change
+pub struct Reference {
+    aircraft: Option<Source<HashMap<String, AircraftInfo>>>,
+    airlines: Option<Source<HashMap<String, String>>>,
+    last_checked: Instant,
+}
+
+impl Reference {
+    /// Returns `None` when neither file is configured
+    pub fn load(
+        aircraft_db: &Option<PathBuf>,
+        airline_db: &Option<PathBuf>,
+    ) -> Result<Option<Reference>, String> {
+        if aircraft_db.is_none() && airline_db.is_none() {
+            return Ok(None);
+        }
+
+        let aircraft = match aircraft_db {
+            Some(path) => Some(Source::load(path, parse_aircraft)?),
+            None => None,
+        };
+        let airlines = match airline_db {
+            Some(path) => Some(Source::load(path, parse_airlines)?),
+            None => None,
+        };
+
+        let reference = Reference {
+            aircraft,
+            airlines,
+            last_checked: Instant::now(),
+        };
+        reference.log_loaded();
+
+        Ok(Some(reference))
+    }
+
+    fn log_loaded(&self) {
+        if let Some(aircraft) = &self.aircraft {
+            info!(
+                "Loaded {} aircraft from {:?}",
+                aircraft.data.len(),
+                aircraft.path
+            );
+        }
+        if let Some(airlines) = &self.airlines {
+            info!(
+                "Loaded {} airlines from {:?}",
+                airlines.data.len(),
+                airlines.path
+            );
+        }
+    }
+
+    /// Picks up changes to the reference files, checking at most every
+    /// `RELOAD_CHECK_SECS`
+    pub fn refresh(&mut self) {
+        if self.last_checked.elapsed() < Duration::from_secs(RELOAD_CHECK_SECS) {
+            return;
+        }
+        self.last_checked = Instant::now();
+
+        let mut reloaded = false;
+        if let Some(aircraft) = self.aircraft.as_mut() {
+            reloaded |= aircraft.refresh(parse_aircraft);
+        }
+        if let Some(airlines) = self.airlines.as_mut() {
+            reloaded |= airlines.refresh(parse_airlines);
+        }
+        if reloaded {
+            self.log_loaded();
+        }
+    }
+
+    pub fn aircraft(&self, icao: &str) -> Option<&AircraftInfo> {
+        self.aircraft
+            .as_ref()
+            .and_then(|aircraft| aircraft.data.get(&icao.to_uppercase()))
+    }
+
+    /// Operator of a flight ID, looked up by its ICAO (three letter) or IATA
+    /// (two character) airline designator
+    pub fn operator(&self, flight_id: &str) -> Option<&str> {
+        let airlines = &self.airlines.as_ref()?.data;
+        let flight_id = flight_id.trim().to_uppercase();
+
+        let icao = flight_id
+            .get(..3)
+            .filter(|code| code.chars().all(|c| c.is_ascii_alphabetic()));
+        icao.and_then(|code| airlines.get(code))
+            .or_else(|| flight_id.get(..2).and_then(|code| airlines.get(code)))
+            .map(String::as_str)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_fields_handle_quotes() {
+        assert_eq!(
+            csv_fields(" A1B2C3 , N12345,B77W "),
+            ["A1B2C3", "N12345", "B77W"]
+        );
+        assert_eq!(
+            csv_fields(r#"ACA,"Air Canada, Inc""#),
+            ["ACA", "Air Canada, Inc"]
+        );
+        assert_eq!(
+            csv_fields(r#"XYZ,"The ""Best"" Air""#),
+            ["XYZ", r#"The "Best" Air"#]
+        );
+        assert_eq!(csv_fields("A1B2C3,,"), ["A1B2C3", "", ""]);
+        assert!(csv_fields("").is_empty());
+        assert!(csv_fields("# comment").is_empty());
+    }
+
+    #[test]
+    fn parse_aircraft_skips_bad_rows() {
+        let aircraft = parse_aircraft(
+            "icao,registration,type\na1b2c3,N12345,B77W\nnot-hex,N1\nABCDEF,,A359\n",
+        )
+        .unwrap();
+
+        assert_eq!(aircraft.len(), 2);
+        assert_eq!(aircraft["A1B2C3"].registration.as_deref(), Some("N12345"));
+        assert_eq!(aircraft["A1B2C3"].aircraft_type.as_deref(), Some("B77W"));
+        assert_eq!(aircraft["ABCDEF"].registration, None);
+    }
+
+    #[test]
+    fn parse_airlines_skips_bad_rows() {
+        let airlines =
+            parse_airlines("code,name\nACA,\"Air Canada, Inc\"\nTOOLONG,Nope\nUA\nua,United\n")
+                .unwrap();
+
+        assert_eq!(airlines.len(), 2);
+        assert_eq!(airlines["ACA"], "Air Canada, Inc");
+        assert_eq!(airlines["UA"], "United");
+    }
+}
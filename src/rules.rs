@@ -0,0 +1,329 @@
+use serde_json::Value;
+
+use crate::frame::{has_error, Entity, MessageFrame};
+
+#[derive(Debug, Clone)]
+enum Condition {
+    /// `spdu`, `lpdu`, `hfnpdu`, `acars` or an LPDU/HFNPDU type name
+    Type(Vec<String>),
+    /// Ground station id or name prefix
+    Gs(Vec<String>),
+    /// ICAO address, registration or flight ID
+    Aircraft(Vec<String>),
+    Label(Vec<String>),
+    Err(bool),
+    /// Inclusive kHz ranges
+    Freq(Vec<(u32, u32)>),
+}
+
+impl Condition {
+    fn parse(spec: &str) -> Result<Condition, String> {
+        let (key, value) = match spec.find('=') {
+            Some(delim) => (spec[..delim].trim(), spec[(delim + 1)..].trim()),
+            None => return Err(format!("Rule condition is not KEY=VALUE: {}", spec)),
+        };
+        let values: Vec<String> = value.split('|').map(|v| v.trim().to_string()).collect();
+
+        match key {
+            "type" => Ok(Condition::Type(
+                values.iter().map(|v| v.to_lowercase()).collect(),
+            )),
+            "gs" => Ok(Condition::Gs(values)),
+            "aircraft" => Ok(Condition::Aircraft(
+                values.iter().map(|v| v.to_uppercase()).collect(),
+            )),
+            "label" => Ok(Condition::Label(values)),
+            "err" => match value {
+                "true" => Ok(Condition::Err(true)),
+                "false" => Ok(Condition::Err(false)),
+                _ => Err(format!("Rule 'err' is not a valid boolean: {}", value)),
+            },
+            "freq" => values
+                .iter()
+                .map(|range| {
+                    let (low, high) = match range.find('-') {
+                        Some(delim) => (&range[..delim], &range[(delim + 1)..]),
+                        None => (range.as_str(), range.as_str()),
+                    };
+                    match (low.parse(), high.parse()) {
+                        (Ok(low), Ok(high)) => Ok((low, high)),
+                        _ => Err(format!("Rule 'freq' is not KHZ or KHZ-KHZ: {}", range)),
+                    }
+                })
+                .collect::<Result<Vec<(u32, u32)>, String>>()
+                .map(Condition::Freq),
+            _ => Err(format!("Unknown rule condition '{}'", key)),
+        }
+    }
+
+    fn matches(&self, msg: Option<&MessageFrame>, frame: &Value) -> bool {
+        if let Condition::Err(err) = self {
+            return has_error(frame) == *err;
+        }
+
+        let msg = match msg {
+            Some(msg) => msg,
+            None => return false,
+        };
+        let lpdu = msg.hfdl.lpdu.as_ref();
+        let hfnpdu = lpdu.and_then(|lpdu| lpdu.hfnpdu.as_ref());
+        let acars = msg.acars();
+
+        match self {
+            Condition::Type(types) => {
+                let mut kinds: Vec<String> = vec![];
+                if msg.hfdl.spdu.is_some() {
+                    kinds.push("spdu".to_string());
+                }
+                if let Some(lpdu) = lpdu {
+                    kinds.push("lpdu".to_string());
+                    kinds.extend(lpdu.kind.as_ref().map(|k| k.name.to_lowercase()));
+                }
+                if let Some(hfnpdu) = hfnpdu {
+                    kinds.push("hfnpdu".to_string());
+                    kinds.extend(hfnpdu.kind.as_ref().map(|k| k.name.to_lowercase()));
+                }
+                if acars.is_some() {
+                    kinds.push("acars".to_string());
+                }
+
+                types.iter().any(|t| kinds.contains(t))
+            }
+            Condition::Gs(stations) => {
+                let entities: Vec<&Entity> = match (&msg.hfdl.spdu, lpdu) {
+                    (Some(spdu), _) => vec![&spdu.src],
+                    (None, Some(lpdu)) => vec![&lpdu.src, &lpdu.dst],
+                    (None, None) => vec![],
+                };
+
+                entities
+                    .into_iter()
+                    .filter(|entity| entity.is_ground_station())
+                    .any(|entity| {
+                        stations.iter().any(|gs| match gs.parse::<u8>() {
+                            Ok(id) => entity.id == id,
+                            Err(_) => entity
+                                .entity_name
+                                .as_ref()
+                                .is_some_and(|name| name.starts_with(gs.as_str())),
+                        })
+                    })
+            }
+            Condition::Aircraft(aircraft) => {
+                let mut idents: Vec<String> = vec![];
+                if let Some(lpdu) = lpdu {
+                    for entity in [&lpdu.src, &lpdu.dst] {
+                        idents.extend(entity.ac_info.as_ref().map(|i| i.icao.clone()));
+                    }
+                    idents.extend(lpdu.ac_info.as_ref().map(|i| i.icao.clone()));
+                }
+                idents.extend(hfnpdu.and_then(|h| h.flight_id.clone()));
+                if let Some(acars) = acars {
+                    idents.extend(acars.registration().map(str::to_string));
+                    idents.extend(acars.flight.clone());
+                }
+
+                idents
+                    .iter()
+                    .any(|ident| aircraft.contains(&ident.to_uppercase()))
+            }
+            Condition::Label(labels) => acars
+                .and_then(|acars| acars.label.as_ref())
+                .is_some_and(|label| labels.contains(label)),
+            Condition::Freq(ranges) => {
+                let freq = msg.hfdl.freq_khz();
+                ranges
+                    .iter()
+                    .any(|(low, high)| (*low..=*high).contains(&freq))
+            }
+            Condition::Err(_) => unreachable!(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    Drop,
+    Route(String),
+    Tag(String),
+}
+
+/// A `CONDITION[,CONDITION...] -> ACTION` frame rule. `*` matches every frame.
+#[derive(Debug, Clone)]
+pub struct Rule {
+    conditions: Vec<Condition>,
+    pub action: Action,
+}
+
+impl Rule {
+    pub fn parse(spec: &str) -> Result<Rule, String> {
+        let (conditions, action) = match spec.find("->") {
+            Some(delim) => (spec[..delim].trim(), spec[(delim + 2)..].trim()),
+            None => return Err(format!("Rule is not CONDITIONS -> ACTION: {}", spec)),
+        };
+
+        let conditions = match conditions {
+            "*" => vec![],
+            _ => conditions
+                .split(',')
+                .map(Condition::parse)
+                .collect::<Result<Vec<Condition>, String>>()?,
+        };
+
+        let action = match action.split_once(':') {
+            None if action == "drop" => Action::Drop,
+            Some(("route", sink)) if !sink.is_empty() => Action::Route(sink.to_string()),
+            Some(("tag", tag)) if !tag.is_empty() => Action::Tag(tag.to_string()),
+            _ => {
+                return Err(format!(
+                    "Rule action is not drop, route:SINK or tag:NAME: {}",
+                    action
+                ))
+            }
+        };
+
+        Ok(Rule { conditions, action })
+    }
+
+    pub fn matches(&self, msg: Option<&MessageFrame>, frame: &Value) -> bool {
+        self.conditions.iter().all(|c| c.matches(msg, frame))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn spdu() -> Value {
+        json!({"hfdl": {"freq": 13276000, "spdu": {
+            "err": false,
+            "src": {"type": "Ground station", "id": 1, "name": "San Francisco, California"},
+            "gs_status": []
+        }}})
+    }
+
+    fn logon_confirm() -> Value {
+        json!({"hfdl": {"freq": 8927000, "lpdu": {
+            "err": false,
+            "src": {"type": "Ground station", "id": 7, "name": "Agana, Guam"},
+            "dst": {"type": "Aircraft", "id": 0},
+            "type": {"id": 159, "name": "Logon confirm"},
+            "ac_info": {"icao": "ABCDEF"},
+            "assigned_ac_id": 23
+        }}})
+    }
+
+    fn acars() -> Value {
+        json!({"hfdl": {"freq": 13312000, "lpdu": {
+            "err": false,
+            "src": {"type": "Aircraft", "id": 23},
+            "dst": {"type": "Ground station", "id": 7, "name": "Agana, Guam"},
+            "type": {"id": 13, "name": "Unnumbered data"},
+            "hfnpdu": {
+                "err": true,
+                "type": {"id": 255, "name": "Enveloped data"},
+                "flight_id": "UA0456",
+                "acars": {"label": "H1", "reg": ".N12345", "flight": "UA0456", "msg_text": "POS"}
+            }
+        }}})
+    }
+
+    fn matches(rule: &str, frame: &Value) -> bool {
+        let rule = Rule::parse(rule).unwrap();
+        let msg = MessageFrame::from_value(frame).ok();
+        rule.matches(msg.as_ref(), frame)
+    }
+
+    #[test]
+    fn parse_actions() {
+        assert_eq!(Rule::parse("* -> drop").unwrap().action, Action::Drop);
+        assert_eq!(
+            Rule::parse("type=spdu -> route:squitters").unwrap().action,
+            Action::Route("squitters".to_string())
+        );
+        assert_eq!(
+            Rule::parse("label=H1 -> tag:position").unwrap().action,
+            Action::Tag("position".to_string())
+        );
+    }
+
+    #[test]
+    fn parse_rejects_malformed() {
+        assert!(Rule::parse("type=spdu").is_err());
+        assert!(Rule::parse("type=spdu -> ").is_err());
+        assert!(Rule::parse("type=spdu -> route:").is_err());
+        assert!(Rule::parse("type=spdu -> tag:").is_err());
+        assert!(Rule::parse("type=spdu -> forward:x").is_err());
+        assert!(Rule::parse("type=spdu -> drop:x").is_err());
+        assert!(Rule::parse("spdu -> drop").is_err());
+        assert!(Rule::parse("color=red -> drop").is_err());
+        assert!(Rule::parse("err=maybe -> drop").is_err());
+        assert!(Rule::parse("freq=high -> drop").is_err());
+        assert!(Rule::parse("freq=8000-x -> drop").is_err());
+    }
+
+    #[test]
+    fn wildcard_matches_everything() {
+        assert!(matches("* -> drop", &spdu()));
+        assert!(matches("* -> drop", &json!({"not": "a frame"})));
+    }
+
+    #[test]
+    fn match_type() {
+        assert!(matches("type=spdu -> drop", &spdu()));
+        assert!(!matches("type=lpdu -> drop", &spdu()));
+        assert!(matches("type=lpdu -> drop", &logon_confirm()));
+        assert!(matches("type=Logon Confirm -> drop", &logon_confirm()));
+        assert!(matches("type=hfnpdu -> drop", &acars()));
+        assert!(matches("type=enveloped data -> drop", &acars()));
+        assert!(matches("type=acars -> drop", &acars()));
+        assert!(!matches("type=acars -> drop", &logon_confirm()));
+    }
+
+    #[test]
+    fn match_alternatives() {
+        assert!(matches("type=lpdu|spdu -> drop", &spdu()));
+        assert!(matches("gs=7|1 -> drop", &spdu()));
+        assert!(matches("label=5Z|H1 -> drop", &acars()));
+        assert!(!matches("label=5Z|80 -> drop", &acars()));
+    }
+
+    #[test]
+    fn match_gs() {
+        assert!(matches("gs=1 -> drop", &spdu()));
+        assert!(matches("gs=San -> drop", &spdu()));
+        assert!(!matches("gs=Agana -> drop", &spdu()));
+        assert!(matches("gs=Agana -> drop", &logon_confirm()));
+        assert!(matches("gs=7 -> drop", &acars()));
+    }
+
+    #[test]
+    fn match_aircraft() {
+        assert!(matches("aircraft=abcdef -> drop", &logon_confirm()));
+        assert!(matches("aircraft=N12345 -> drop", &acars()));
+        assert!(matches("aircraft=ua0456 -> drop", &acars()));
+        assert!(!matches("aircraft=N12345 -> drop", &spdu()));
+    }
+
+    #[test]
+    fn match_err_and_freq() {
+        assert!(matches("err=true -> drop", &acars()));
+        assert!(matches("err=false -> drop", &spdu()));
+        assert!(matches(
+            "err=true -> drop",
+            &json!({"hfdl": {"lpdu": {"err": true}}})
+        ));
+
+        assert!(matches("freq=13276 -> drop", &spdu()));
+        assert!(matches("freq=13000-13300 -> drop", &spdu()));
+        assert!(!matches("freq=13000-13300 -> drop", &acars()));
+        assert!(matches("freq=8000-9000|13312 -> drop", &acars()));
+    }
+
+    #[test]
+    fn all_conditions_must_match() {
+        assert!(matches("type=acars, label=H1, gs=Agana -> drop", &acars()));
+        assert!(!matches("type=acars,label=H1,gs=San -> drop", &acars()));
+    }
+}
@@ -0,0 +1,253 @@
+use log::*;
+use std::collections::HashMap;
+use std::io::Write;
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::aircraft::{AircraftState, AircraftTable};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// What was last sent to a client for an aircraft, so only changes are sent
+#[derive(Default)]
+struct Sent {
+    position: Option<u64>,
+    callsign: Option<String>,
+}
+
+/// A connected client and what it has been sent so far. Clients connecting
+/// late start out with everything currently known.
+struct Client {
+    stream: TcpStream,
+    sent: HashMap<String, Sent>,
+}
+
+/// BaseStation date (`YYYY/MM/DD`) and time (`HH:MM:SS.mmm`) columns of a
+/// UTC timestamp in milliseconds
+fn date_time(millis: u64) -> (String, String) {
+    let (days, ms) = ((millis / 86_400_000) as i64, millis % 86_400_000);
+
+    // Civil date from days since 1970-01-01 in the proleptic Gregorian calendar
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    (
+        format!("{:04}/{:02}/{:02}", year, month, day),
+        format!(
+            "{:02}:{:02}:{:02}.{:03}",
+            ms / 3_600_000,
+            ms / 60_000 % 60,
+            ms / 1000 % 60,
+            ms % 1000
+        ),
+    )
+}
+
+/// Formats a BaseStation `MSG` line; `fields` fill columns 11 to 22
+fn message(kind: u8, state: &AircraftState, generated: u64, fields: &[String]) -> String {
+    let (generated_date, generated_time) = date_time(generated * 1000);
+    let logged = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as u64);
+    let (logged_date, logged_time) = date_time(logged);
+
+    format!(
+        "MSG,{},1,1,{},1,{},{},{},{},{}\r\n",
+        kind,
+        state.icao.to_uppercase(),
+        generated_date,
+        generated_time,
+        logged_date,
+        logged_time,
+        fields.join(",")
+    )
+}
+
+/// Identification (`MSG,1`) and airborne position (`MSG,3`) lines for
+/// everything that changed since the last poll
+fn changes(state: &AircraftState, sent: &mut Sent) -> Vec<String> {
+    let mut lines = vec![];
+
+    let callsign = state.flight_id.as_ref().or(state.registration.as_ref());
+    if callsign.is_some() && callsign != sent.callsign.as_ref() {
+        let mut fields = vec![String::new(); 12];
+        fields[0] = callsign.cloned().unwrap_or_default();
+        lines.push(message(1, state, state.last_seen, &fields));
+        sent.callsign = callsign.cloned();
+    }
+
+    if let (Some(lat), Some(lon), Some(updated)) = (state.lat, state.lon, state.position_updated) {
+        if sent.position != Some(updated) {
+            let mut fields = vec![String::new(); 12];
+            fields[1] = state.altitude.map_or(String::new(), |alt| alt.to_string());
+            fields[4] = format!("{:.5}", lat);
+            fields[5] = format!("{:.5}", lon);
+            fields[11] = "0".to_string();
+            lines.push(message(3, state, updated, &fields));
+            sent.position = Some(updated);
+        }
+    }
+
+    lines
+}
+
+/// Sends every client what changed for it since it was last sent to, dropping
+/// clients that have disconnected
+fn broadcast(table: &AircraftTable, clients: &mut Vec<Client>) {
+    clients.retain_mut(|client| {
+        let lines: String = table
+            .active()
+            .flat_map(|state| changes(state, client.sent.entry(state.icao.clone()).or_default()))
+            .collect();
+        client
+            .sent
+            .retain(|icao, _| table.active().any(|state| &state.icao == icao));
+        if lines.is_empty() {
+            return true;
+        }
+
+        match client.stream.write_all(lines.as_bytes()) {
+            Ok(_) => true,
+            Err(e) => {
+                info!("SBS client disconnected: {}", e);
+                false
+            }
+        }
+    });
+}
+
+/// Serves aircraft identification and positions as BaseStation (SBS-1) CSV
+/// lines to every connected TCP client
+pub fn start(addr: &str, table: Arc<Mutex<AircraftTable>>) -> Result<(), String> {
+    let listener = TcpListener::bind(addr)
+        .map_err(|e| format!("Unable to bind SBS output to {}: {}", addr, e))?;
+    info!("SBS output listening on {}", addr);
+
+    let clients: Arc<Mutex<Vec<Client>>> = Arc::new(Mutex::new(vec![]));
+
+    let accepted = Arc::clone(&clients);
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    info!("SBS client connected: {:?}", stream.peer_addr());
+                    if let Err(e) = stream.set_write_timeout(Some(WRITE_TIMEOUT)) {
+                        warn!("Unable to set SBS client write timeout: {}", e);
+                    }
+                    accepted.lock().unwrap().push(Client {
+                        stream,
+                        sent: HashMap::new(),
+                    });
+                }
+                Err(e) => error!("SBS output failed to accept connection: {}", e),
+            }
+        }
+    });
+
+    thread::spawn(move || loop {
+        thread::sleep(POLL_INTERVAL);
+
+        let table = table.lock().unwrap();
+        broadcast(&table, &mut clients.lock().unwrap());
+    });
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::MessageFrame;
+    use serde_json::json;
+    use std::io::Read;
+
+    fn table() -> AircraftTable {
+        let frame = json!({"hfdl": {"freq": 13276000, "lpdu": {
+            "err": false,
+            "src": {"type": "Aircraft", "id": 12, "ac_info": {"icao": "a1b2c3"}},
+            "dst": {"type": "Ground station", "id": 1},
+            "hfnpdu": {"flight_id": "UAL123", "pos": {"lat": 35.5, "lon": -140.25}}
+        }}});
+
+        let mut table = AircraftTable::new(Duration::from_secs(600), None);
+        table.update(&MessageFrame::from_value(&frame).unwrap(), &frame);
+        table
+    }
+
+    /// A connected client and the end reading what it is sent
+    fn connect(listener: &TcpListener) -> (Client, TcpStream) {
+        let reader = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        reader
+            .set_read_timeout(Some(Duration::from_millis(200)))
+            .unwrap();
+        let (stream, _) = listener.accept().unwrap();
+        let client = Client {
+            stream,
+            sent: HashMap::new(),
+        };
+        (client, reader)
+    }
+
+    /// Lines received, split into columns
+    fn received(reader: &mut TcpStream) -> Vec<Vec<String>> {
+        let mut buf = [0u8; 4096];
+        let size = reader.read(&mut buf).unwrap_or(0);
+        String::from_utf8_lossy(&buf[..size])
+            .lines()
+            .map(|line| line.split(',').map(str::to_string).collect())
+            .collect()
+    }
+
+    fn kinds(lines: &[Vec<String>]) -> Vec<&str> {
+        lines.iter().map(|columns| columns[1].as_str()).collect()
+    }
+
+    #[test]
+    fn formats_dates_and_times() {
+        assert_eq!(
+            date_time(0),
+            ("1970/01/01".to_string(), "00:00:00.000".to_string())
+        );
+        assert_eq!(
+            date_time(951_827_696_789),
+            ("2000/02/29".to_string(), "12:34:56.789".to_string())
+        );
+        assert_eq!(
+            date_time(1_704_067_199_999),
+            ("2023/12/31".to_string(), "23:59:59.999".to_string())
+        );
+    }
+
+    #[test]
+    fn late_clients_are_sent_known_aircraft() {
+        let table = table();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+
+        let (early, mut early_reader) = connect(&listener);
+        let mut clients = vec![early];
+        broadcast(&table, &mut clients);
+        assert_eq!(kinds(&received(&mut early_reader)), vec!["1", "3"]);
+
+        let (late, mut late_reader) = connect(&listener);
+        clients.push(late);
+        broadcast(&table, &mut clients);
+        let lines = received(&mut late_reader);
+        assert_eq!(kinds(&lines), vec!["1", "3"]);
+        assert_eq!(lines[0][4], "A1B2C3");
+        assert_eq!(lines[0][10], "UAL123");
+        assert_eq!(lines[1][14..16], ["35.50000", "-140.25000"]);
+
+        // Nothing changed for the client that already has it all
+        assert!(received(&mut early_reader).is_empty());
+    }
+}
@@ -0,0 +1,163 @@
+use crossbeam::channel::{self, bounded, Sender};
+use log::*;
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::config::{Config, Tuning};
+use crate::receiver::{Receiver, ReceiverLine, ReceiverStatus};
+
+/// A dumphfdl that exits sooner than this after being launched is not
+/// restarted on its own, since it would most likely fail again right away
+const MIN_RESTART_UPTIME: Duration = Duration::from_secs(30);
+
+/// Set of dumphfdl receivers listening to the bands chosen for a session
+pub struct Session {
+    pub receivers: Vec<Receiver>,
+    pub lines: channel::Receiver<ReceiverLine>,
+
+    /// Kept to hand to receivers restarted during the session
+    line_send: Sender<ReceiverLine>,
+}
+
+impl Session {
+    /// Spawns a receiver for every (SDR index, band, tuning) entry. Returns
+    /// `None` if none of the receivers could be started.
+    pub fn start(
+        config: &Config,
+        systable: &Path,
+        allocation: &[(usize, u32, Tuning)],
+    ) -> Option<Session> {
+        let (line_send, line_recv) = bounded::<ReceiverLine>(2048);
+
+        let mut receivers: Vec<Receiver> = vec![];
+        for (sdr_idx, band, tuning) in allocation.iter() {
+            match Receiver::spawn(
+                *sdr_idx,
+                config,
+                systable,
+                *band,
+                tuning.clone(),
+                line_send.clone(),
+            ) {
+                Ok(receiver) => {
+                    info!(
+                        "New session started: sdr={} band={} freqs={:?}",
+                        config.sdrs[*sdr_idx], band, receiver.status.freqs
+                    );
+                    receivers.push(receiver);
+                }
+                Err(e) => error!("Receiver {} failed to start: {}", sdr_idx, e),
+            }
+        }
+
+        if receivers.is_empty() {
+            return None;
+        }
+
+        Some(Session {
+            receivers,
+            lines: line_recv,
+            line_send,
+        })
+    }
+
+    /// Relaunches the dumphfdl of a receiver that exited with the same band
+    /// and tuning, leaving the other receivers running
+    pub fn restart(
+        &mut self,
+        sdr_idx: usize,
+        config: &Config,
+        systable: &Path,
+    ) -> Result<(), String> {
+        let pos = match self.receivers.iter().position(|r| r.status.id == sdr_idx) {
+            Some(pos) => pos,
+            None => return Err(format!("Receiver {} is not part of the session", sdr_idx)),
+        };
+
+        let exited = self.receivers.remove(pos);
+        let uptime = exited.started.elapsed();
+        let (band, tuning) = (exited.status.band, exited.tuning.clone());
+        exited.stop();
+
+        if uptime < MIN_RESTART_UPTIME {
+            return Err(format!(
+                "Receiver {} exited {}s after launch",
+                sdr_idx,
+                uptime.as_secs()
+            ));
+        }
+
+        let receiver = Receiver::spawn(
+            sdr_idx,
+            config,
+            systable,
+            band,
+            tuning,
+            self.line_send.clone(),
+        )?;
+        info!(
+            "Restarted receiver: sdr={} band={}",
+            config.sdrs[sdr_idx], band
+        );
+        self.receivers.insert(pos, receiver);
+
+        Ok(())
+    }
+
+    pub fn allocation(&self) -> Vec<(usize, u32)> {
+        self.receivers
+            .iter()
+            .map(|r| (r.status.id, r.status.band))
+            .collect()
+    }
+
+    /// Frequency correction in effect for every receiver
+    pub fn freq_corrections(&self) -> HashMap<usize, f64> {
+        self.receivers
+            .iter()
+            .filter_map(|r| r.status.freq_correction.map(|ppm| (r.status.id, ppm)))
+            .collect()
+    }
+
+    /// Gain each band is being received with (`None` for autogain)
+    pub fn gains(&self) -> HashMap<u32, Option<f64>> {
+        self.receivers
+            .iter()
+            .map(|r| (r.status.band, r.status.gain))
+            .collect()
+    }
+
+    /// Whether the learned gain or frequency correction of any receiver
+    /// differs from what it was launched with
+    pub fn retuned(&self, tuned: &[(usize, u32, Tuning)]) -> bool {
+        tuned.iter().any(|(sdr_idx, _, tuning)| {
+            self.receivers
+                .iter()
+                .find(|r| r.status.id == *sdr_idx)
+                .is_some_and(|r| {
+                    r.status.freq_correction != tuning.freq_correction
+                        || r.status.gain != tuning.gain
+                })
+        })
+    }
+
+    pub fn band_of(&self, sdr_idx: usize) -> Option<u32> {
+        self.receivers
+            .iter()
+            .find(|r| r.status.id == sdr_idx)
+            .map(|r| r.status.band)
+    }
+
+    pub fn status(&self) -> Vec<ReceiverStatus> {
+        self.receivers.iter().map(|r| r.status.clone()).collect()
+    }
+
+    pub fn stop(self) {
+        // Unblock reader threads waiting on a full channel before joining them
+        drop(self.lines);
+        for receiver in self.receivers.into_iter() {
+            receiver.stop();
+        }
+    }
+}
@@ -0,0 +1,103 @@
+use log::*;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// Clients that take longer than this to send a request or read the response
+/// are dropped so they cannot stall the API
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(5);
+
+type Provider = Box<dyn Fn() -> Value + Send + Sync>;
+
+/// Minimal read-only HTTP server exposing JSON snapshots of viper's state
+pub struct StatusServer {
+    providers: BTreeMap<String, Provider>,
+}
+
+impl StatusServer {
+    pub fn new() -> Self {
+        StatusServer {
+            providers: BTreeMap::new(),
+        }
+    }
+
+    pub fn register<F>(&mut self, path: &str, provider: F)
+    where
+        F: Fn() -> Value + Send + Sync + 'static,
+    {
+        self.providers.insert(path.to_string(), Box::new(provider));
+    }
+
+    pub fn serve(self, addr: &str) -> Result<(), String> {
+        let listener = TcpListener::bind(addr)
+            .map_err(|e| format!("Unable to bind status server to {}: {}", addr, e))?;
+        info!("Status API listening on http://{}", addr);
+
+        let server = Arc::new(self);
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                match stream {
+                    Ok(stream) => {
+                        let server = Arc::clone(&server);
+                        thread::spawn(move || {
+                            if let Err(e) = server.handle(stream) {
+                                warn!("Status API request failed: {}", e);
+                            }
+                        });
+                    }
+                    Err(e) => error!("Status API failed to accept connection: {}", e),
+                }
+            }
+        });
+
+        Ok(())
+    }
+
+    fn handle(&self, mut stream: TcpStream) -> std::io::Result<()> {
+        stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+        stream.set_write_timeout(Some(CLIENT_TIMEOUT))?;
+
+        let mut request = String::new();
+        BufReader::new(&stream).read_line(&mut request)?;
+
+        let path = request.split_whitespace().nth(1).unwrap_or("/");
+        let path = path.split('?').next().unwrap_or(path);
+
+        let (status, body) = match path {
+            "/" => (
+                "200 OK",
+                json!({ "endpoints": self.providers.keys().collect::<Vec<&String>>() }),
+            ),
+            _ => match self.providers.get(path) {
+                Some(provider) => ("200 OK", provider()),
+                None => ("404 Not Found", json!({ "error": "not found" })),
+            },
+        };
+
+        let body = body.to_string();
+        write!(
+            stream,
+            "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            body.len(),
+            body
+        )
+    }
+}
+
+/// Registers a provider that locks `state` and renders it with `render`
+pub fn provide<T, F>(server: &mut StatusServer, path: &str, state: &Arc<Mutex<T>>, render: F)
+where
+    T: Send + 'static,
+    F: Fn(&T) -> Value + Send + Sync + 'static,
+{
+    let state = Arc::clone(state);
+    server.register(path, move || match state.lock() {
+        Ok(state) => render(&state),
+        Err(_) => Value::Null,
+    });
+}
@@ -0,0 +1,46 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+use crate::aircraft::AircraftState;
+
+/// A single aircraft position as recorded in the track log
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TrackPoint {
+    pub ts: u64,
+    pub icao: String,
+    pub flight_id: Option<String>,
+    pub registration: Option<String>,
+    pub lat: f64,
+    pub lon: f64,
+    pub altitude: Option<i64>,
+    pub gs: u32,
+    pub freq: u32,
+}
+
+impl TrackPoint {
+    pub fn from_state(state: &AircraftState) -> Option<TrackPoint> {
+        Some(TrackPoint {
+            ts: state.position_updated?,
+            icao: state.icao.clone(),
+            flight_id: state.flight_id.clone(),
+            registration: state.registration.clone(),
+            lat: state.lat?,
+            lon: state.lon?,
+            altitude: state.altitude,
+            gs: state.gs,
+            freq: state.freq,
+        })
+    }
+}
+
+/// Reads every point of a track log, skipping lines that fail to parse
+pub fn read(path: &Path) -> Result<Vec<TrackPoint>, String> {
+    let contents = fs::read_to_string(path)
+        .map_err(|e| format!("Unable to read track log {:?}: {}", path, e))?;
+
+    Ok(contents
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
@@ -0,0 +1,51 @@
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const EXIT_TIMEOUT: Duration = Duration::from_secs(30);
+
+fn testing(file: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("testing")
+        .join(file)
+}
+
+#[test]
+fn iq_file_emits_frames_and_exits_when_recording_ends() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_viper"))
+        .arg("--bin")
+        .arg(testing("dumphfdl-stub"))
+        .arg("--sys-table")
+        .arg(testing("systable.json"))
+        .arg("--iq-file")
+        .arg(testing("recording.cs16"))
+        .args(["--iq-format", "cs16"])
+        .args(["--iq-sample-rate", "256000"])
+        .args(["--iq-centerfreq", "13300"])
+        .args(["--chooser", "single:band=13"])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("viper should start");
+
+    let started = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait().unwrap() {
+            break status;
+        }
+        if started.elapsed() > EXIT_TIMEOUT {
+            child.kill().unwrap();
+            panic!("viper did not exit after the recording ended");
+        }
+        thread::sleep(Duration::from_millis(100));
+    };
+    assert!(status.success(), "viper exited with {}", status);
+
+    let output = child.wait_with_output().unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let expected = std::fs::read_to_string(testing("frames.jsonl")).unwrap();
+
+    let frames: Vec<&str> = stdout.lines().collect();
+    assert_eq!(frames, expected.lines().collect::<Vec<&str>>());
+}
@@ -42,6 +42,7 @@ if __name__ == "__main__":
             "name": name,
             "lat": station["lat"],
             "lon": station["lon"],
+            "frequencies": sorted(int(freq) for freq in station["frequencies"]),
         }
 
         for freq in sorted(station["frequencies"]):